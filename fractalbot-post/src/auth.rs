@@ -0,0 +1,74 @@
+use anyhow::{Context, Result};
+use log::info;
+use megalodon::{megalodon::AppInputOptions, SNS::Mastodon};
+
+/// Redirect URI telling the instance to display the authorization code to the user.
+const OUT_OF_BAND: &str = "urn:ietf:wg:oauth:2.0:oob";
+
+/// OAuth scopes needed for posting, answering mentions, polls and profile updates.
+pub const SCOPES: [&str; 7] = [
+    "read:accounts",
+    "read:notifications",
+    "read:statuses",
+    "write:accounts",
+    "write:media",
+    "write:notifications",
+    "write:statuses",
+];
+
+/// An application registered with an instance.
+#[derive(Debug, Clone)]
+pub struct App {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+/// Register an application called `client_name` with the instance.
+///
+/// Returns the application and the URL at which the bot's account can authorize it.
+pub async fn register_app(
+    instance_url: String,
+    client_name: String,
+    website: Option<String>,
+    user_agent: String,
+) -> Result<(App, String)> {
+    let client = megalodon::generator(Mastodon, instance_url, None, Some(user_agent))?;
+
+    info!("Registering application {client_name}...");
+    let options = AppInputOptions {
+        scopes: Some(SCOPES.map(String::from).to_vec()),
+        redirect_uris: Some(OUT_OF_BAND.into()),
+        website,
+    };
+    let app = client
+        .register_app(client_name, &options)
+        .await
+        .context("Failed to register application")?;
+
+    let url = app
+        .url
+        .context("Instance did not return an authorization URL")?;
+    let app = App {
+        client_id: app.client_id,
+        client_secret: app.client_secret,
+    };
+    Ok((app, url))
+}
+
+/// Exchange an authorization `code` for an access token.
+pub async fn fetch_access_token(
+    instance_url: String,
+    app: App,
+    code: String,
+    user_agent: String,
+) -> Result<String> {
+    let client = megalodon::generator(Mastodon, instance_url, None, Some(user_agent))?;
+
+    info!("Fetching access token...");
+    let token = client
+        .fetch_access_token(app.client_id, app.client_secret, code, OUT_OF_BAND.into())
+        .await
+        .context("Failed to fetch access token")?;
+
+    Ok(token.access_token)
+}
@@ -1,35 +1,89 @@
+use std::io::Cursor;
 use std::sync::Arc;
 
 use anyhow::{Context, Result};
 use log::info;
 use megalodon::{
-    entities::{Attachment, UploadMedia},
+    entities::{notification::NotificationType, Attachment, UploadMedia},
     error::{self, Error, OwnError},
-    megalodon::PostStatusInputOptions,
+    megalodon::{
+        GetNotificationsInputOptions, PollOptions, PostStatusInputOptions, PostStatusOutput,
+    },
     Megalodon,
     SNS::Mastodon,
 };
+use reqwest::multipart::{Form, Part};
 
-pub use megalodon::entities::StatusVisibility;
+pub use megalodon::entities::{Poll, StatusVisibility};
 
 use crate::retry::{retry, Retry};
 
 pub struct Client {
     pub client: Arc<dyn Megalodon + Send + Sync>,
+    http: reqwest::Client,
+    instance_url: String,
+    access_token: String,
+}
+
+/// A status mentioning the bot's account.
+#[derive(Debug, Clone)]
+pub struct Mention {
+    /// ID of the notification that informed us about the mention.
+    pub notification_id: String,
+    /// ID of the status containing the mention.
+    pub status_id: String,
+    /// Account (`user@instance`) that wrote the status.
+    pub account: String,
+    /// HTML content of the status.
+    pub content: String,
+    pub visibility: StatusVisibility,
+    /// Whether the account is marked as a bot.
+    pub bot: bool,
 }
 
 impl Client {
     pub fn new(instance_url: String, access_token: String, user_agent: String) -> Result<Self> {
-        let client: Arc<_> =
-            megalodon::generator(Mastodon, instance_url, Some(access_token), Some(user_agent))?
-                .into();
-        Ok(Self { client })
+        let http = reqwest::Client::builder()
+            .user_agent(user_agent.clone())
+            .build()?;
+        let client: Arc<_> = megalodon::generator(
+            Mastodon,
+            instance_url.clone(),
+            Some(access_token.clone()),
+            Some(user_agent),
+        )?
+        .into();
+        Ok(Self {
+            client,
+            http,
+            instance_url,
+            access_token,
+        })
+    }
+
+    /// Check that the access token works, returning the name of the bot's account.
+    pub async fn verify_credentials(&self) -> Result<String> {
+        let account = retry(Retry::any(), || async {
+            self.client
+                .verify_account_credentials()
+                .await
+                .map(|res| res.json())
+        })
+        .await
+        .context("Failed to verify credentials")?;
+
+        Ok(account.acct)
     }
 
-    async fn upload_image(&self, image_data: &'static [u8], file_name: &'static str) -> Result<UploadMedia> {
+    async fn upload_image(
+        &self,
+        image_data: Arc<[u8]>,
+        file_name: &'static str,
+    ) -> Result<UploadMedia> {
         retry(Retry::any(), || async {
+            let reader = Cursor::new(image_data.clone());
             self.client
-                .upload_media_reader(Box::new(image_data), None, Some(file_name.into()))
+                .upload_media_reader(Box::new(reader), None, Some(file_name.into()))
                 .await
                 .map(|res| res.json())
         })
@@ -60,35 +114,29 @@ impl Client {
         }
     }
 
+    /// Post a status, returning its ID.
     pub async fn post_status(
         &self,
-        media_id: String,
         description: String,
-        visibility: StatusVisibility,
-    ) -> Result<()> {
+        options: PostStatusInputOptions,
+    ) -> Result<String> {
         retry(Retry::any(), || async {
             self.client
-                .post_status(
-                    description.clone(),
-                    Some(&PostStatusInputOptions {
-                        media_ids: Some(vec![media_id.clone()]),
-                        visibility: Some(visibility.clone()),
-                        ..Default::default()
-                    }),
-                )
+                .post_status(description.clone(), Some(&options))
                 .await
-                .map(drop)
+                .map(|res| match res.json() {
+                    PostStatusOutput::Status(status) => status.id,
+                    PostStatusOutput::ScheduledStatus(status) => status.id,
+                })
         })
         .await
     }
 
-    pub async fn post_status_with_image(
+    async fn upload_and_resolve_image(
         &self,
-        image_data: &'static [u8],
+        image_data: Arc<[u8]>,
         file_name: &'static str,
-        description: String,
-        visibility: StatusVisibility,
-    ) -> Result<()> {
+    ) -> Result<Attachment> {
         info!("Uploading image...");
         let media = self
             .upload_image(image_data, file_name)
@@ -103,9 +151,243 @@ impl Client {
 
         info!("Uploaded image has ID {}", media.id);
 
+        Ok(media)
+    }
+
+    pub async fn post_status_with_image(
+        &self,
+        image_data: Arc<[u8]>,
+        file_name: &'static str,
+        description: String,
+        visibility: StatusVisibility,
+    ) -> Result<String> {
+        let media = self.upload_and_resolve_image(image_data, file_name).await?;
+
         info!("Posting status...");
-        self.post_status(media.id, description, visibility)
+        let options = PostStatusInputOptions {
+            media_ids: Some(vec![media.id]),
+            visibility: Some(visibility),
+            ..Default::default()
+        };
+        self.post_status(description, options)
             .await
             .context("Failed to post status")
     }
+
+    /// Reply to `mention` with a status containing an image.
+    ///
+    /// The reply has the same visibility as the mention, except that
+    /// replies to public statuses are unlisted so as to not flood public timelines.
+    pub async fn reply_with_image(
+        &self,
+        mention: &Mention,
+        image_data: Arc<[u8]>,
+        file_name: &'static str,
+        description: String,
+    ) -> Result<()> {
+        let media = self.upload_and_resolve_image(image_data, file_name).await?;
+
+        info!("Replying to status {}...", mention.status_id);
+        let options = PostStatusInputOptions {
+            media_ids: Some(vec![media.id]),
+            in_reply_to_id: Some(mention.status_id.clone()),
+            visibility: Some(reply_visibility(&mention.visibility)),
+            ..Default::default()
+        };
+        self.post_status(description, options)
+            .await
+            .context("Failed to post reply")
+            .map(drop)
+    }
+
+    /// Reply to `mention` with a text-only status.
+    pub async fn reply(&self, mention: &Mention, description: String) -> Result<()> {
+        info!("Replying to status {}...", mention.status_id);
+        let options = PostStatusInputOptions {
+            in_reply_to_id: Some(mention.status_id.clone()),
+            visibility: Some(reply_visibility(&mention.visibility)),
+            ..Default::default()
+        };
+        self.post_status(description, options)
+            .await
+            .context("Failed to post reply")
+            .map(drop)
+    }
+
+    /// Send a direct message to `account` (`user` or `user@instance`).
+    pub async fn send_direct_message(&self, account: &str, text: String) -> Result<()> {
+        info!("Sending direct message to {account}...");
+        let options = PostStatusInputOptions {
+            visibility: Some(StatusVisibility::Direct),
+            ..Default::default()
+        };
+        let account = account.trim_start_matches('@');
+        self.post_status(format!("@{account} {text}"), options)
+            .await
+            .context("Failed to send direct message")
+            .map(drop)
+    }
+
+    /// Post a poll in reply to the status `in_reply_to_id`, returning the ID of the poll's status.
+    ///
+    /// Mastodon does not allow statuses with both media attachments and a poll,
+    /// so polls about an image are posted as a reply to it.
+    pub async fn post_poll(
+        &self,
+        in_reply_to_id: String,
+        question: String,
+        choices: Vec<String>,
+        expires_in: u64,
+        visibility: StatusVisibility,
+    ) -> Result<String> {
+        info!("Posting poll...");
+        let options = PostStatusInputOptions {
+            poll: Some(PollOptions {
+                options: choices,
+                expires_in: Some(expires_in),
+                ..Default::default()
+            }),
+            in_reply_to_id: Some(in_reply_to_id),
+            visibility: Some(visibility),
+            ..Default::default()
+        };
+        self.post_status(question, options)
+            .await
+            .context("Failed to post poll")
+    }
+
+    /// Fetch the poll attached to the status `status_id`, if there is one.
+    pub async fn poll(&self, status_id: String) -> Result<Option<Poll>> {
+        let status = retry(Retry::any(), || async {
+            self.client
+                .get_status(status_id.clone())
+                .await
+                .map(|res| res.json())
+        })
+        .await
+        .context("Failed to fetch poll")?;
+
+        Ok(status.poll)
+    }
+
+    async fn patch_profile_images(
+        &self,
+        avatar: Option<&[u8]>,
+        header: Option<&[u8]>,
+    ) -> reqwest::Result<()> {
+        let image_part = |data: &[u8], file_name: &'static str| {
+            Part::bytes(data.to_vec())
+                .file_name(file_name)
+                .mime_str("image/png")
+        };
+
+        let mut form = Form::new();
+        if let Some(avatar) = avatar {
+            form = form.part("avatar", image_part(avatar, "avatar.png")?);
+        }
+        if let Some(header) = header {
+            form = form.part("header", image_part(header, "header.png")?);
+        }
+
+        let url = format!(
+            "{}/api/v1/accounts/update_credentials",
+            self.instance_url.trim_end_matches('/')
+        );
+        self.http
+            .patch(url)
+            .bearer_auth(&self.access_token)
+            .multipart(form)
+            .send()
+            .await?
+            .error_for_status()
+            .map(drop)
+    }
+
+    /// Replace the avatar and/or header of the bot's account with PNG images.
+    ///
+    /// `megalodon` can only send profile images as plain strings,
+    /// so they are uploaded as a multipart form instead.
+    pub async fn update_profile_images(
+        &self,
+        avatar: Option<Arc<[u8]>>,
+        header: Option<Arc<[u8]>>,
+    ) -> Result<()> {
+        info!("Updating profile images...");
+        retry(Retry::any(), || async {
+            self.patch_profile_images(avatar.as_deref(), header.as_deref())
+                .await
+                .map_err(Error::from)
+        })
+        .await
+        .context("Failed to update profile images")
+    }
+
+    /// Fetch notifications about mentions of the bot's account, oldest first.
+    ///
+    /// If `min_id` is given, only notifications newer than it are returned.
+    pub async fn mentions(&self, min_id: Option<String>) -> Result<Vec<Mention>> {
+        let options = GetNotificationsInputOptions {
+            limit: Some(40),
+            min_id,
+            exclude_types: Some(vec![
+                NotificationType::Follow,
+                NotificationType::FollowRequest,
+                NotificationType::Reblog,
+                NotificationType::Favourite,
+                NotificationType::PollVote,
+                NotificationType::PollExpired,
+                NotificationType::Status,
+                NotificationType::Update,
+            ]),
+            ..Default::default()
+        };
+
+        let mut notifications = retry(Retry::any(), || async {
+            self.client
+                .get_notifications(Some(&options))
+                .await
+                .map(|res| res.json())
+        })
+        .await
+        .context("Failed to fetch notifications")?;
+
+        notifications.sort_by_key(|notification| notification.created_at);
+
+        let mentions = notifications
+            .into_iter()
+            .filter(|notification| notification.r#type == NotificationType::Mention)
+            .filter_map(|notification| {
+                let status = notification.status?;
+                Some(Mention {
+                    notification_id: notification.id,
+                    status_id: status.id,
+                    account: status.account.acct,
+                    content: status.content,
+                    visibility: status.visibility,
+                    bot: status.account.bot,
+                })
+            })
+            .collect();
+
+        Ok(mentions)
+    }
+
+    /// Clear a notification, so that it is not returned by [Client::mentions] again.
+    pub async fn dismiss_notification(&self, notification_id: &str) -> Result<()> {
+        retry(Retry::any(), || async {
+            self.client
+                .dismiss_notification(notification_id.into())
+                .await
+                .map(drop)
+        })
+        .await
+        .context("Failed to dismiss notification")
+    }
+}
+
+fn reply_visibility(visibility: &StatusVisibility) -> StatusVisibility {
+    match visibility {
+        StatusVisibility::Public => StatusVisibility::Unlisted,
+        visibility => visibility.clone(),
+    }
 }
@@ -1,4 +1,7 @@
+pub mod auth;
 mod retry;
 mod client;
+mod webhook;
 
-pub use crate::client::{Client as Client, StatusVisibility};
+pub use crate::client::{Client as Client, Mention, Poll, StatusVisibility};
+pub use crate::webhook::post_webhook;
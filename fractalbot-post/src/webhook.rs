@@ -0,0 +1,26 @@
+use anyhow::{Context, Result};
+use megalodon::error::Error;
+use serde_json::json;
+
+use crate::retry::{retry, Retry};
+
+/// Post `text` to a chat webhook.
+///
+/// The payload `{"text": ...}` is understood by Slack, Mattermost, Discord (via `/slack`)
+/// and most webhook bridges.
+pub async fn post_webhook(url: &str, text: &str, user_agent: String) -> Result<()> {
+    let http = reqwest::Client::builder().user_agent(user_agent).build()?;
+    let payload = json!({ "text": text });
+
+    retry(Retry::any(), || async {
+        http.post(url)
+            .json(&payload)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map(drop)
+            .map_err(Error::from)
+    })
+    .await
+    .context("Failed to post to webhook")
+}
@@ -0,0 +1,115 @@
+use std::fmt::{self, Display};
+use std::ops::{Range, RangeInclusive};
+
+use crate::{
+    complex::Complex,
+    float::{Lanes, Real},
+};
+
+/// Frequencies of [Average::Stripes] sampled if none is requested.
+pub(crate) const FREQUENCIES: RangeInclusive<u32> = 2..=10;
+
+/// Weights of averages against the distance sampled if none is requested
+/// (see [Sampling::mix](crate::Sampling::mix)).
+pub(crate) const MIXES: Range<f64> = 0.3..0.8;
+
+/// A function of the iterates of an orbit, averaged along it for coloring
+/// (see [Orbit::average](crate::distance_estimation::Orbit::average)).
+///
+/// Following Härkönen's "On smooth fractal coloring techniques", the average of the
+/// terms up to `z_n` is blended with the one up to `z_{n-1}` by how far the orbit
+/// escaped, so that it varies smoothly across bands of equal iteration counts.
+/// Terms take values in [0, 1] and only need the last three iterates `z_n`,
+/// `z_{n-1}` and `z_{n-2}`, which any iteration of `z² + c` can provide.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Average {
+    /// Stripe average: `(1 + sin(frequency · arg z_n)) / 2`.
+    Stripes { frequency: u32 },
+    /// Curvature average: `(1 - cos θ) / 2`, where θ is the angle the orbit turns by
+    /// at `z_{n-1}`. Like Härkönen's `|θ| / π`, it grows with `|θ|`, but it needs no
+    /// arctangent, which SIMD lanes have no exact version of.
+    Curvature,
+}
+
+impl Average {
+    /// Term of the iterate `z` following `previous`, which follows `before`.
+    pub(crate) fn term<T: Real>(
+        &self,
+        z: Complex<T>,
+        previous: Complex<T>,
+        before: Complex<T>,
+    ) -> T {
+        let half = T::from_f64(0.5);
+        match *self {
+            Self::Stripes { frequency } => {
+                // sin(k·arg z) is the imaginary part of (z / |z|)^k.
+                let norm = (z.re * z.re + z.im * z.im).sqrt();
+                let (u_re, u_im) = (z.re / norm, z.im / norm);
+                let (mut re, mut im) = (u_re, u_im);
+                for _ in 1..frequency {
+                    (re, im) = (re * u_re - im * u_im, re * u_im + im * u_re);
+                }
+                half + half * im
+            }
+            Self::Curvature => {
+                let (a_re, a_im) = (z.re - previous.re, z.im - previous.im);
+                let (b_re, b_im) = (previous.re - before.re, previous.im - before.im);
+                let dot = a_re * b_re + a_im * b_im;
+                let norms = ((a_re * a_re + a_im * a_im) * (b_re * b_re + b_im * b_im)).sqrt();
+                half - half * (dot / norms)
+            }
+        }
+    }
+
+    /// Like [Average::term], for iterates in lanes, performing the same operations.
+    pub(crate) fn term_lanes<T: Real>(
+        &self,
+        (re, im): (T::Lanes, T::Lanes),
+        (previous_re, previous_im): (T::Lanes, T::Lanes),
+        (before_re, before_im): (T::Lanes, T::Lanes),
+    ) -> T::Lanes {
+        let half = <T::Lanes as Lanes<T>>::splat(T::from_f64(0.5));
+        match *self {
+            Self::Stripes { frequency } => {
+                let norm = (re * re + im * im).sqrt();
+                let (u_re, u_im) = (re / norm, im / norm);
+                let (mut re, mut im) = (u_re, u_im);
+                for _ in 1..frequency {
+                    (re, im) = (re * u_re - im * u_im, re * u_im + im * u_re);
+                }
+                half + half * im
+            }
+            Self::Curvature => {
+                let (a_re, a_im) = (re - previous_re, im - previous_im);
+                let (b_re, b_im) = (previous_re - before_re, previous_im - before_im);
+                let dot = a_re * b_re + a_im * b_im;
+                let norms = ((a_re * a_re + a_im * a_im) * (b_re * b_re + b_im * b_im)).sqrt();
+                half - half * (dot / norms)
+            }
+        }
+    }
+
+    /// Average of the terms of an orbit summing to `sum`, the last of which is `last`,
+    /// after `terms` terms. `weight` in [0, 1] is how far the orbit escaped: 1 right after
+    /// passing the escape radius, falling to 0 as it escapes by a whole iteration.
+    pub(crate) fn smooth(sum: f64, last: f64, terms: usize, weight: f64) -> f64 {
+        match terms {
+            0 => 0.0,
+            1 => sum,
+            n => {
+                let average = sum / n as f64;
+                let previous = (sum - last) / (n - 1) as f64;
+                weight * average + (1.0 - weight) * previous
+            }
+        }
+    }
+}
+
+impl Display for Average {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stripes { frequency } => write!(f, "stripes of frequency {frequency}"),
+            Self::Curvature => f.write_str("curvature"),
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use std::ops::Range;
+
+use image::{ImageBuffer, Pixel};
+use rayon::prelude::{IndexedParallelIterator, ParallelIterator, ParallelSliceMut};
+
+use crate::{complex::Complex, float::Real, symmetry::GridSymmetry};
+
+/// Number of rows rendered as one unit of parallel work.
+pub const ROWS_PER_TILE: u32 = 4;
+
+/// A rectangle in the complex plane, with corners in precision `T`.
+///
+/// Sets are framed in double precision; boxes are only converted (with
+/// [BoundingBox::cast]) to map pixels to points in the precision of a render.
+#[derive(Debug, Clone)]
+pub struct BoundingBox<T = f64> {
+    min: Complex<T>,
+    max: Complex<T>,
+}
+
+impl<T: Real> Default for BoundingBox<T> {
+    fn default() -> Self {
+        Self {
+            min: Complex::new(T::zero(), T::zero()),
+            max: Complex::new(T::zero(), T::zero()),
+        }
+    }
+}
+
+impl<T: Real> BoundingBox<T> {
+    fn update(&mut self, p: &Complex<T>) {
+        let min = self.min;
+        let max = self.max;
+        self.min = Complex::new(p.re.min(min.re), p.im.min(min.im));
+        self.max = Complex::new(p.re.max(max.re), p.im.max(max.im));
+    }
+
+    /// The same box in precision `U`.
+    pub fn cast<U: Real>(&self) -> BoundingBox<U> {
+        let cast = |z: Complex<T>| Complex::new(U::from_f64(z.re.into()), U::from_f64(z.im.into()));
+        BoundingBox {
+            min: cast(self.min),
+            max: cast(self.max),
+        }
+    }
+
+    pub fn offset(&self, point: &Complex<T>) -> Complex<T> {
+        point - self.min
+    }
+
+    pub fn abs_dimension(&self) -> Complex<T> {
+        self.offset(&self.max)
+    }
+
+    /// Largest absolute value of the coordinates of points in the box.
+    pub fn magnitude(&self) -> T {
+        [self.min.re, self.min.im, self.max.re, self.max.im]
+            .into_iter()
+            .fold(T::zero(), |max, x| max.max(x.abs()))
+    }
+
+    pub fn aspect_ratio(&self) -> f64 {
+        let d = self.abs_dimension();
+        (d.re / d.im).into()
+    }
+
+    pub fn width_for(&self, height: u32) -> u32 {
+        let height: f64 = height.into();
+
+        (height * self.aspect_ratio()) as u32
+    }
+
+    pub fn height_for(&self, width: u32) -> u32 {
+        let width: f64 = width.into();
+
+        (width * (1.0 / self.aspect_ratio())) as u32
+    }
+
+    /// The largest size within `width`×`height` with the aspect ratio of the box,
+    /// at least one pixel wide and tall unless `width` or `height` is 0.
+    pub fn fit(&self, width: u32, height: u32) -> (u32, u32) {
+        if self.aspect_ratio() > 1.0 {
+            // Bounding box is wider than tall
+            (width, self.height_for(width).max(1))
+        } else {
+            (self.width_for(height).max(1), height)
+        }
+    }
+
+    pub fn center(&self) -> Complex<T> {
+        (self.min + self.max) * T::from_f64(0.5)
+    }
+
+    pub fn point_from_grid(&self, x: u32, y: u32, width: u32, height: u32) -> Complex<T> {
+        let x_rel = T::from_f64(f64::from(x) / f64::from(width));
+        let y_rel = T::from_f64(f64::from(y) / f64::from(height));
+
+        let dim = self.abs_dimension();
+
+        let rel = Complex::new(dim.re * x_rel, dim.im * y_rel);
+        self.min + rel
+    }
+
+    /// The part of the box shown by the pixels `columns`×`rows` of an image
+    /// of size `width`×`height` spanning the box.
+    pub fn region(
+        &self,
+        columns: Range<u32>,
+        rows: Range<u32>,
+        width: u32,
+        height: u32,
+    ) -> BoundingBox<T> {
+        BoundingBox {
+            min: self.point_from_grid(columns.start, rows.start, width, height),
+            max: self.point_from_grid(columns.end, rows.end, width, height),
+        }
+    }
+
+    /// Split `image` into horizontal bands of [ROWS_PER_TILE] rows, to be rendered in parallel.
+    ///
+    /// Bands are small enough for rayon's work stealing to balance cheap (exterior)
+    /// and expensive (interior) parts of the set between threads.
+    /// The partition only depends on the size of the image, not on the number of threads.
+    pub fn par_tiles<'b, 'p, P>(
+        &'b self,
+        image: &'p mut ImageBuffer<P, Vec<P::Subpixel>>,
+    ) -> impl IndexedParallelIterator<Item = Tile<'p, 'b, P, T>>
+    where
+        P: Pixel + Send + Sync,
+        P::Subpixel: Send + Sync,
+    {
+        let (width, height) = image.dimensions();
+        let tile_len = width as usize * usize::from(P::CHANNEL_COUNT) * ROWS_PER_TILE as usize;
+
+        image
+            .par_chunks_mut(tile_len)
+            .enumerate()
+            .map(move |(index, samples)| Tile {
+                width,
+                height,
+                y: index as u32 * ROWS_PER_TILE,
+                samples,
+                bbx: self,
+            })
+    }
+}
+
+impl BoundingBox {
+    pub fn scale(&mut self, scale: f64) {
+        self.min *= scale;
+        self.max *= scale;
+    }
+
+    /// The largest box with the given aspect ratio inside this one, sharing its center.
+    pub fn crop_to(&self, aspect_ratio: f64) -> BoundingBox {
+        let dim = self.abs_dimension();
+        let dim = if self.aspect_ratio() > aspect_ratio {
+            Complex::new(dim.im * aspect_ratio, dim.im)
+        } else {
+            Complex::new(dim.re, dim.re / aspect_ratio)
+        };
+
+        let center = self.center();
+        Self {
+            min: center - dim * 0.5,
+            max: center + dim * 0.5,
+        }
+    }
+
+    /// Inverse of [BoundingBox::point_from_grid], rounded to the nearest grid point.
+    pub fn grid_from_point(&self, point: &Complex, width: u32, height: u32) -> (u32, u32) {
+        let rel = self.offset(point);
+        let dim = self.abs_dimension();
+
+        let x = (rel.re / dim.re * f64::from(width)).round() as u32;
+        let y = (rel.im / dim.im * f64::from(height)).round() as u32;
+        (x.min(width), y.min(height))
+    }
+
+    /// The pixel rectangle `(x, y, width, height)` covered by `inner`
+    /// in an image of size `width`×`height` spanning this box.
+    pub fn grid_rect(&self, inner: &BoundingBox, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let (x_min, y_min) = self.grid_from_point(&inner.min, width, height);
+        let (x_max, y_max) = self.grid_from_point(&inner.max, width, height);
+        (x_min, y_min, x_max - x_min, y_max - y_min)
+    }
+
+    /// Shift the box so that the point reflection z → -z maps the pixel grid
+    /// of an image of size `width`×`height` onto itself, where it overlaps its mirror image.
+    ///
+    /// The box moves by at most a quarter of a pixel along each axis, and only
+    /// if the mirror image of the box overlaps it at all.
+    pub fn snap_symmetric(&mut self, width: u32, height: u32) -> GridSymmetry {
+        let dim = self.abs_dimension();
+        let pixel = Complex::new(dim.re / f64::from(width), dim.im / f64::from(height));
+
+        // Pixel x shows z = min + x * pixel, and -z = min + (a - x) * pixel.
+        let a = -2.0 * self.min.re / pixel.re;
+        let b = -2.0 * self.min.im / pixel.im;
+
+        let overlaps = |a: f64, len: u32| 0.0 <= a.round() && a.round() < 2.0 * f64::from(len);
+        if !(overlaps(a, width) && overlaps(b, height)) {
+            return GridSymmetry::none(width, height);
+        }
+
+        let (a, b) = (a.round(), b.round());
+        self.min = Complex::new(-0.5 * a * pixel.re, -0.5 * b * pixel.im);
+        self.max = self.min + dim;
+
+        GridSymmetry::new(a as u32, b as u32, width, height)
+    }
+}
+
+impl<T: Real> FromIterator<Complex<T>> for BoundingBox<T> {
+    fn from_iter<I: IntoIterator<Item = Complex<T>>>(iter: I) -> Self {
+        let mut bbx = Self::default();
+        iter.into_iter().for_each(|p| bbx.update(&p));
+        bbx
+    }
+}
+
+/// A band of consecutive rows of an image spanning a [BoundingBox].
+pub struct Tile<'p, 'b, P, T = f64>
+where
+    P: Pixel,
+{
+    width: u32,
+    height: u32,
+    /// Index of the first row of the tile.
+    y: u32,
+    samples: &'p mut [P::Subpixel],
+    bbx: &'b BoundingBox<T>,
+}
+
+impl<P, T> Tile<'_, '_, P, T>
+where
+    P: Pixel,
+    T: Real,
+{
+    /// Number of pixels in the tile.
+    pub fn pixels(&self) -> usize {
+        self.samples.len() / usize::from(P::CHANNEL_COUNT)
+    }
+
+    /// Pixels of the tile in row-major order, together with their
+    /// coordinates and the points they sample.
+    pub fn points(&mut self) -> impl Iterator<Item = (u32, u32, &mut P, Complex<T>)> {
+        let Self {
+            width,
+            height,
+            y,
+            bbx,
+            ..
+        } = *self;
+
+        self.samples
+            .chunks_exact_mut(usize::from(P::CHANNEL_COUNT))
+            .enumerate()
+            .map(move |(index, pixel)| {
+                let x = index as u32 % width;
+                let y = y + index as u32 / width;
+                let point = bbx.point_from_grid(x, y, width, height);
+                (x, y, P::from_slice_mut(pixel), point)
+            })
+    }
+}
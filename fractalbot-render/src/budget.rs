@@ -0,0 +1,256 @@
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use rand::{Rng, RngExt};
+
+use crate::{
+    average::{FREQUENCIES, MIXES},
+    bounding_box::BoundingBox,
+    color::{Palette, PaletteChoice},
+    progress::Timings,
+    pyramid::Level,
+    render::{MAX_ITER, RenderRequest, Renderer},
+    sampling::Sampling,
+    trap::TrapChoice,
+};
+
+/// Number of pixels of the preview rendered to estimate the cost of a render.
+const PREVIEW_PIXELS: u32 = 128 * 128;
+
+/// Lowest iteration limit a render is degraded to.
+const MIN_ITER: usize = MAX_ITER / 8;
+
+/// Smallest fraction of the requested width and height a render is shrunk to.
+const MIN_SCALE: f64 = 0.5;
+
+/// Number of parameters tried before rendering over budget.
+const MAX_ATTEMPTS: usize = 5;
+
+/// Factor the image is shrunk by at a time until its estimated cost fits.
+const SHRINK: f64 = 0.9;
+
+/// What a render produces, which decides how many pixels it renders.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Output {
+    Image,
+    /// A tile pyramid, rendering every level of it.
+    Pyramid,
+}
+
+/// Cost of rendering a Julia set, measured on a preview.
+///
+/// Every pixel is sampled, but only pixels near the boundary of the set (and at sharp
+/// edges) are refined. Their number grows with the resolution to the power of the
+/// dimension of the boundary, between 1 (a smooth curve) and 2 (filling the plane),
+/// so a small preview refines a larger share of its pixels than the full render.
+#[derive(Debug, Copy, Clone)]
+struct Cost {
+    /// Time to set up rendering, whatever the size.
+    setup: Duration,
+    /// Time to sample a pixel.
+    sampling: Duration,
+    /// Time to refine a pixel.
+    refinement: Duration,
+    /// Number of pixels refined in the preview.
+    refined: f64,
+    /// Width and height of the preview, in pixels, as if it were square.
+    preview: f64,
+    /// Dimension of the boundary, as estimated from the preview.
+    dimension: f64,
+}
+
+impl Cost {
+    /// Estimated time to render an image of size `width`×`height`, or a pyramid of it.
+    fn of(&self, (width, height): (u32, u32), output: Output) -> Duration {
+        let image = |width: u32, height: u32| {
+            let pixels = f64::from(width) * f64::from(height);
+            let refined = self.refined * (pixels.sqrt() / self.preview).powf(self.dimension);
+            self.sampling.mul_f64(pixels) + self.refinement.mul_f64(refined)
+        };
+        let rendering = match output {
+            Output::Image => image(width, height),
+            Output::Pyramid => Level::pyramid(width, height)
+                .map(|level| image(level.width, level.height))
+                .sum(),
+        };
+        self.setup + rendering
+    }
+}
+
+impl RenderRequest {
+    /// Fix the choices left open by the request (the parameter, the palette, the trap,
+    /// the parameters of averages and the light).
+    ///
+    /// If the request has a [budget](RenderRequest::budget), the cost of rendering is
+    /// estimated from a small preview first. While it exceeds the time left, the
+    /// iteration limit, then the number of samples per pixel, and finally the size of
+    /// the image are reduced. If that is not enough, a different parameter is sampled,
+    /// unless one was requested explicitly.
+    ///
+    /// The returned request has no budget, so rendering it does not estimate again.
+    pub fn plan<R: Rng + ?Sized>(&self, rng: &mut R, timings: &mut Timings) -> RenderRequest {
+        self.plan_for(rng, timings, Output::Image)
+    }
+
+    /// Like [RenderRequest::plan], estimating the cost of rendering `output`.
+    pub(crate) fn plan_for<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        timings: &mut Timings,
+        output: Output,
+    ) -> RenderRequest {
+        let start = Instant::now();
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+            let c = timings.time("sampling", || self.sample_parameter(rng));
+            let palette = rng.sample(self.palette);
+            let trap = rng.sample(self.trap);
+            let stripes = self
+                .stripes
+                .unwrap_or_else(|| rng.random_range(FREQUENCIES));
+            let mix = self.mix.unwrap_or_else(|| rng.random_range(MIXES));
+            let light = self.light.unwrap_or_else(|| rng.random_range(0.0..360.0));
+            let mut request = RenderRequest {
+                parameter: Some(c),
+                palette: PaletteChoice::Named(palette),
+                budget: None,
+                trap: TrapChoice::Fixed(trap),
+                stripes: Some(stripes),
+                mix: Some(mix),
+                light: Some(light),
+                sampling: Sampling {
+                    trap,
+                    stripes,
+                    mix,
+                    light,
+                    ..self.sampling
+                },
+                ..self.clone()
+            };
+
+            let Some(budget) = self.budget else {
+                return request;
+            };
+
+            let bbx = timings.time("bounding", || self.frame(c));
+            let fits = timings.time("estimation", || {
+                request.degrade(palette, &bbx, output, budget, start)
+            });
+
+            if fits {
+                return request;
+            }
+            // The Mandelbrot set does not depend on the parameter.
+            if self.parameter.is_some() || self.sampling.mandelbrot || attempt == MAX_ATTEMPTS {
+                warn!("Rendering is likely to exceed the budget of {budget:.2?}");
+                return request;
+            }
+            info!("Sampling a different parameter to fit the budget");
+        }
+    }
+
+    /// Reduce the quality of the render until its estimated cost fits into what is
+    /// left of `budget` since `start`. Returns whether it fits.
+    fn degrade(
+        &mut self,
+        palette: Palette,
+        bbx: &BoundingBox,
+        output: Output,
+        budget: Duration,
+        start: Instant,
+    ) -> bool {
+        let remaining = || budget.saturating_sub(start.elapsed());
+        let assess = |request: &Self| {
+            let cost = request.measure(palette, bbx);
+            (
+                cost,
+                cost.of(bbx.fit(request.width, request.height), output),
+            )
+        };
+
+        let (mut cost, mut estimate) = assess(self);
+        info!(
+            "Estimated render time: {estimate:.2?} ({:.2?} left)",
+            remaining()
+        );
+
+        while estimate > remaining() && self.max_iter > MIN_ITER {
+            self.max_iter = (self.max_iter / 2).max(MIN_ITER);
+            (cost, estimate) = assess(self);
+            info!(
+                "Reduced iteration limit to {} (estimated {estimate:.2?})",
+                self.max_iter
+            );
+        }
+
+        while estimate > remaining() && self.sampling.grid() > 1 {
+            self.sampling.samples = (self.sampling.grid() / 2).pow(2);
+            (cost, estimate) = assess(self);
+            info!(
+                "Reduced to {} samples per pixel (estimated {estimate:.2?})",
+                self.sampling.samples
+            );
+        }
+
+        if estimate > remaining() {
+            // The cost model covers any size, so there is no need to measure again.
+            let (width, height) = (self.width, self.height);
+            let mut scale = 1.0;
+            while estimate > remaining() && scale > MIN_SCALE {
+                scale = (scale * SHRINK).max(MIN_SCALE);
+                let shrink = |len: u32| ((f64::from(len) * scale) as u32).max(1);
+                (self.width, self.height) = (shrink(width), shrink(height));
+                estimate = cost.of(bbx.fit(self.width, self.height), output);
+            }
+            info!(
+                "Reduced maximum size to {}x{} (estimated {estimate:.2?})",
+                self.width, self.height
+            );
+        }
+
+        estimate <= remaining()
+    }
+
+    /// Measure the [Cost] of rendering, by timing a preview of at most [PREVIEW_PIXELS]
+    /// pixels. The dimension of the boundary is estimated by comparing the number of
+    /// pixels refined in it to the number refined in a preview of half its size.
+    fn measure(&self, palette: Palette, bbx: &BoundingBox) -> Cost {
+        let c = self
+            .parameter
+            .expect("parameter is fixed before estimating");
+
+        let (width, height) = bbx.fit(self.width, self.height);
+        let pixels = f64::from(width) * f64::from(height);
+        let scale = (f64::from(PREVIEW_PIXELS) / pixels).sqrt().min(1.0);
+        let preview_width = ((f64::from(width) * scale).ceil() as u32).max(1);
+        let preview_height = ((f64::from(height) * scale).ceil() as u32).max(1);
+        let preview_pixels = f64::from(preview_width) * f64::from(preview_height);
+
+        let start = Instant::now();
+        let renderer = Renderer::quiet(c, palette, self.max_iter, &self.sampling);
+        let setup = start.elapsed();
+        let (_, refined) = renderer.render_view(bbx, preview_width, preview_height);
+        let timings = renderer.finish();
+
+        let renderer = Renderer::quiet(c, palette, self.max_iter, &self.sampling);
+        let half = |len: u32| len.div_ceil(2);
+        let (_, coarse) = renderer.render_view(bbx, half(preview_width), half(preview_height));
+        renderer.finish();
+
+        let dimension = if refined > 0 && coarse > 0 {
+            (refined as f64 / coarse as f64).log2().clamp(1.0, 2.0)
+        } else {
+            1.0
+        };
+        Cost {
+            setup,
+            sampling: timings.get("rendering").div_f64(preview_pixels),
+            refinement: timings.get("refinement").div_f64(refined.max(1) as f64),
+            refined: refined as f64,
+            preview: preview_pixels.sqrt(),
+            dimension,
+        }
+    }
+}
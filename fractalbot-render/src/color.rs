@@ -1,3 +1,7 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use anyhow::{Error, bail};
 use cgmath::{Vector3, prelude::*, vec3};
 use rand::{RngExt, distr::Distribution, seq::IndexedRandom};
 use rand_distr::{Pert, Uniform};
@@ -15,7 +19,7 @@ fn vec3_to_rgb(v: Vec3) -> image::Rgb<u8> {
     image::Rgb(v.map(conv).into())
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Palette {
     a: Vec3,
     b: Vec3,
@@ -28,6 +32,33 @@ impl Palette {
         let color = palette_vec(t, &self.a, &self.b, &self.c, &self.d);
         vec3_to_rgb(color)
     }
+
+    /// Like [Palette::pick], but without quantizing the color.
+    pub fn pick_srgb(&self, t: f64) -> Srgb {
+        let color = palette_vec(t, &self.a, &self.b, &self.c, &self.d);
+        color.map(|v| v.clamp(0.0, 1.0) as f32).into()
+    }
+}
+
+/// A color with sRGB-encoded channels in [0, 1].
+pub type Srgb = [f32; 3];
+
+/// Decode an sRGB-encoded channel into linear light.
+pub fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encode a channel in linear light as sRGB.
+pub fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.003_130_8 {
+        12.92 * v
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
 }
 
 pub const RAINBOW: Palette = Palette {
@@ -74,6 +105,17 @@ pub const GLOW: Palette = Palette {
 };
 
 const DEFAULT_PALETTES: [Palette; 7] = [RAINBOW, WHITES, ARCTIC, CITRUS, DUSK, PINK, GLOW];
+pub const DEFAULT_PALETTE_NAMES: [&str; 7] = [
+    "rainbow", "whites", "arctic", "citrus", "dusk", "pink", "glow",
+];
+
+/// Look up one of the [DEFAULT_PALETTES] by its (case-insensitive) name.
+pub fn named_palette(name: &str) -> Option<Palette> {
+    DEFAULT_PALETTE_NAMES
+        .iter()
+        .position(|palette_name| palette_name.eq_ignore_ascii_case(name))
+        .map(|index| DEFAULT_PALETTES[index])
+}
 
 pub struct DefaultPalettes;
 
@@ -138,3 +180,59 @@ impl Distribution<Palette> for MonotonePalette {
         }
     }
 }
+
+/// How to pick the palette of a render: either a fixed, named palette,
+/// or a random sample from one of the palette distributions.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum PaletteChoice {
+    Named(Palette),
+    Presets,
+    PhaseShift,
+    #[default]
+    Monotone,
+}
+
+impl FromStr for PaletteChoice {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(palette) = named_palette(s) {
+            return Ok(Self::Named(palette));
+        }
+
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "presets" => Self::Presets,
+            "phase-shift" => Self::PhaseShift,
+            "monotone" => Self::Monotone,
+            _ => bail!(
+                "Unknown palette {s:?}, expected one of {names} or a family (presets, phase-shift, monotone)",
+                names = DEFAULT_PALETTE_NAMES.join(", ")
+            ),
+        })
+    }
+}
+
+impl Display for PaletteChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Named(palette) => match DEFAULT_PALETTES.iter().position(|p| p == palette) {
+                Some(index) => f.write_str(DEFAULT_PALETTE_NAMES[index]),
+                None => write!(f, "{palette:.2?}"),
+            },
+            Self::Presets => f.write_str("presets"),
+            Self::PhaseShift => f.write_str("phase-shift"),
+            Self::Monotone => f.write_str("monotone"),
+        }
+    }
+}
+
+impl Distribution<Palette> for PaletteChoice {
+    fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> Palette {
+        match self {
+            Self::Named(palette) => *palette,
+            Self::Presets => DefaultPalettes.sample(rng),
+            Self::PhaseShift => PhaseShiftPalette.sample(rng),
+            Self::Monotone => MonotonePalette.sample(rng),
+        }
+    }
+}
@@ -0,0 +1,436 @@
+use std::f64::consts::TAU;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use anyhow::{Error, bail};
+
+use crate::{
+    average::Average,
+    color::{Palette, Srgb, linear_to_srgb, srgb_to_linear},
+    distance_estimation::{AttractingCycle, Orbit},
+    sampling::Sampling,
+};
+
+/// Number of iterations over which [IterationColorizer] cycles through the palette once.
+const ITERATIONS_PER_CYCLE: f64 = 16.0;
+
+/// How fast colors fade with the distance of orbits from the trap.
+const TRAP_SHARPNESS: f64 = 8.0;
+
+/// Number of field lines drawn by [DecompositionColorizer] per turn of `z_n`, which
+/// are `FIELD_LINES · 2^n` external rays: even far from the set, `n` is around 5.
+const FIELD_LINES: f64 = 2.0;
+
+/// Width of field lines, as a fraction of the space between neighbouring lines.
+const LINE_WIDTH: f64 = 0.1;
+
+/// Brightness (in linear light) of the darker half of binary decomposition
+/// and of every other equipotential band.
+const DARKENING: f32 = 0.4;
+
+/// Brightness (in linear light) of the darkened palette [InteriorColorizer] picks from.
+const INTERIOR_BRIGHTNESS: f32 = 0.35;
+
+/// Turns what the orbit of a point outside the set reveals into its color.
+///
+/// Points in the interior (distance ≤ 0) are colored by an [InteriorColorizer]
+/// instead, so other colorizers only deal with points outside the set. Colorizers pick
+/// colors from a palette rather than owning one, so that any palette can be
+/// combined with any coloring.
+pub trait Colorizer: Send + Sync {
+    fn color(&self, orbit: &Orbit, palette: &Palette) -> Srgb;
+}
+
+/// Colors by the distance to the set, fading into the palette away from it.
+#[derive(Debug, Copy, Clone)]
+pub struct DistanceColorizer {
+    /// How fast colors fade with the distance from the set.
+    pub sharpness: f64,
+}
+
+impl DistanceColorizer {
+    /// Connected sets are larger and fill more of the image, so their colors fade slower.
+    pub fn new(connected: bool) -> Self {
+        let sharpness = if connected { 25.0 } else { 100.0 };
+        Self { sharpness }
+    }
+
+    fn position(&self, orbit: &Orbit) -> f64 {
+        self.fade(orbit.distance)
+    }
+
+    fn fade(&self, distance: f64) -> f64 {
+        squeeze((self.sharpness * distance).sqrt())
+    }
+}
+
+impl Colorizer for DistanceColorizer {
+    fn color(&self, orbit: &Orbit, palette: &Palette) -> Srgb {
+        palette.pick_srgb(self.position(orbit))
+    }
+}
+
+/// Colors escaping points by their [smooth iteration count](Orbit::smooth_iterations),
+/// cycling through the palette in bands. Points that did not escape are black.
+#[derive(Debug, Copy, Clone)]
+pub struct IterationColorizer;
+
+impl IterationColorizer {
+    fn position(&self, orbit: &Orbit) -> Option<f64> {
+        orbit
+            .smooth_iterations()
+            .map(|iterations| iterations / ITERATIONS_PER_CYCLE)
+    }
+}
+
+impl Colorizer for IterationColorizer {
+    fn color(&self, orbit: &Orbit, palette: &Palette) -> Srgb {
+        match self.position(orbit) {
+            Some(t) => palette.pick_srgb(t),
+            None => [0.0; 3],
+        }
+    }
+}
+
+/// Blends the positions in the palette picked by distance and by iteration count.
+#[derive(Debug, Copy, Clone)]
+pub struct BlendColorizer {
+    pub distance: DistanceColorizer,
+    /// Weight of the iteration count against the distance, in [0, 1].
+    pub weight: f64,
+}
+
+impl Colorizer for BlendColorizer {
+    fn color(&self, orbit: &Orbit, palette: &Palette) -> Srgb {
+        let distance = self.distance.position(orbit);
+        match IterationColorizer.position(orbit) {
+            Some(iterations) => {
+                palette.pick_srgb((1.0 - self.weight) * distance + self.weight * iterations)
+            }
+            None => [0.0; 3],
+        }
+    }
+}
+
+/// Colors by how close orbits come to a [Trap](crate::trap::Trap), optionally
+/// shaded by the distance to the set (fading to black away from it).
+#[derive(Debug, Copy, Clone)]
+pub struct TrapColorizer {
+    pub shading: Option<DistanceColorizer>,
+}
+
+impl Colorizer for TrapColorizer {
+    fn color(&self, orbit: &Orbit, palette: &Palette) -> Srgb {
+        let color = palette.pick_srgb(squeeze(TRAP_SHARPNESS * orbit.trap));
+        match self.shading {
+            Some(distance) => darken(color, distance.position(orbit) as f32),
+            None => color,
+        }
+    }
+}
+
+/// Colors by the distance, decomposed by the argument of the escaped iterate `z_n`.
+///
+/// Binary decomposition darkens points whose `z_n` lies below the real axis; field lines
+/// are drawn where the argument is a multiple of `2π / FIELD_LINES`. Since
+/// `arg z_{n+1} ≈ 2 arg z_n`, lines continue from band to band of equal iteration
+/// count, branching in two towards the set, and follow the external rays.
+/// Equipotential bands darken every other level set of the potential `log|z_n| / 2^n`,
+/// which are the bands of equal [smooth iteration count](Orbit::smooth_iterations).
+/// Points that did not escape are colored by the distance alone.
+#[derive(Debug, Copy, Clone)]
+pub struct DecompositionColorizer {
+    pub distance: DistanceColorizer,
+    /// Whether to draw field lines instead of splitting bands in two.
+    pub field_lines: bool,
+    /// Whether to darken every other equipotential band.
+    pub bands: bool,
+}
+
+impl Colorizer for DecompositionColorizer {
+    fn color(&self, orbit: &Orbit, palette: &Palette) -> Srgb {
+        let color = palette.pick_srgb(self.distance.position(orbit));
+        let Some(iterations) = orbit.smooth_iterations() else {
+            return color;
+        };
+
+        let mut brightness = if self.field_lines {
+            let turns = (orbit.z.arg() / TAU).rem_euclid(1.0);
+            let offset = (FIELD_LINES * turns).fract();
+            // Distance to the nearest line, relative to the space between lines.
+            let distance = offset.min(1.0 - offset);
+            (distance / LINE_WIDTH).min(1.0) as f32
+        } else if orbit.z.im < 0.0 {
+            DARKENING
+        } else {
+            1.0
+        };
+        if self.bands && iterations.rem_euclid(2.0) >= 1.0 {
+            brightness *= DARKENING;
+        }
+        darken(color, brightness)
+    }
+}
+
+/// Colors points in the interior of a connected set by how their orbits approach
+/// the attracting cycle, from a darkened variant of the palette.
+///
+/// An orbit approaches the point `p_j` of the cycle closest to its last iterate `z_n`,
+/// if that lies within the [capture radius](AttractingCycle::capture_radius) `r`.
+/// Interior points of sets without an attracting cycle, or whose orbits never came
+/// that close, are black (as is the whole interior without an interior colorizer).
+#[derive(Debug, Clone)]
+pub struct InteriorColorizer {
+    pub interior: Interior,
+    pub cycle: Option<AttractingCycle>,
+    /// Fades [Interior::Distance] like the distance outside the set.
+    pub distance: DistanceColorizer,
+}
+
+impl InteriorColorizer {
+    fn position(&self, orbit: &Orbit) -> Option<f64> {
+        let cycle = self.cycle.as_ref()?;
+        let (index, offset) = cycle
+            .points
+            .iter()
+            .map(|&point| orbit.z - point)
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.norm_sqr().total_cmp(&b.norm_sqr()))?;
+        let gap = offset.norm();
+        if gap >= cycle.capture_radius() {
+            return None;
+        }
+
+        let period = cycle.period();
+        let iterations = orbit.iterations;
+        let position = match self.interior {
+            Interior::Convergence => {
+                // Every period brings orbits closer by |λ|. Counting the fraction of a
+                // period left until `gap` shrinks below `r` keeps the count continuous
+                // where orbits are captured one period later. A superattracting cycle
+                // (λ = 0) squares the gap every period instead, up to a constant factor.
+                // Orbits landing right on the cycle count as infinitely close to it.
+                let (gap, radius) = (gap.max(f64::MIN_POSITIVE), cycle.capture_radius());
+                let contraction = cycle.multiplier.norm();
+                let periods = if contraction > 0.0 {
+                    (gap / radius).ln() / contraction.ln()
+                } else {
+                    (gap.ln() / radius.ln()).log2()
+                };
+                (iterations as f64 - period as f64 * periods) / ITERATIONS_PER_CYCLE
+            }
+            Interior::Distance => self.distance.fade(cycle.capture_radius() / orbit.entry),
+            Interior::Angle => {
+                let angle = offset.arg() - (iterations / period) as f64 * cycle.multiplier.arg();
+                (angle / TAU).rem_euclid(1.0)
+            }
+            Interior::Cycle => {
+                let point = (index + period - iterations % period) % period;
+                point as f64 / period as f64
+            }
+        };
+        Some(position)
+    }
+}
+
+impl Colorizer for InteriorColorizer {
+    fn color(&self, orbit: &Orbit, palette: &Palette) -> Srgb {
+        match self.position(orbit) {
+            Some(t) => darken(palette.pick_srgb(t), INTERIOR_BRIGHTNESS),
+            None => [0.0; 3],
+        }
+    }
+}
+
+/// Choice of how [InteriorColorizer] colors the interior of connected sets.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Interior {
+    /// By the number of iterations orbits take to approach the cycle, smoothed by
+    /// how close they come.
+    Convergence,
+    /// By the estimated distance to the Julia set, `r / |z_e'|` with `z_e'` the
+    /// derivative where the orbit [entered](Orbit::entry) the capture radius.
+    ///
+    /// Near the cycle, the branch of the inverse of the iteration leading back to the
+    /// point is one-to-one on a disk of about the capture radius (the orbit of the
+    /// critical point crowds the cycle closer), so by Koebe's ¼-theorem the point lies
+    /// at least about `r / 4|z_e'|` inside the set. Unlike `|z_n - p_j| / |z_n'|`, which
+    /// agrees with it while orbits follow the linearization of the cycle, this does not
+    /// vanish at points landing right on the cycle.
+    Distance,
+    /// By the angle at which orbits approach the cycle, turned back by the
+    /// argument of the multiplier once per period.
+    Angle,
+    /// By the point of the cycle the orbit of the point converges to in step with it.
+    Cycle,
+}
+
+impl Interior {
+    pub const NAMES: [&str; 4] = ["convergence", "distance", "angle", "cycle"];
+}
+
+impl FromStr for Interior {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "convergence" => Ok(Self::Convergence),
+            "distance" => Ok(Self::Distance),
+            "angle" => Ok(Self::Angle),
+            "cycle" => Ok(Self::Cycle),
+            _ => bail!(
+                "Unknown interior coloring {s:?}, expected one of {names}",
+                names = Self::NAMES.join(", ")
+            ),
+        }
+    }
+}
+
+impl Display for Interior {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Convergence => "convergence",
+            Self::Distance => "distance",
+            Self::Angle => "angle",
+            Self::Cycle => "cycle",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Scale `color` by `brightness` in linear light.
+fn darken(color: Srgb, brightness: f32) -> Srgb {
+    color.map(|channel| linear_to_srgb(brightness * srgb_to_linear(channel)))
+}
+
+/// Mixes the [average](Orbit::average) along orbits into the position in the palette
+/// picked by distance, revealing textures like stripes.
+#[derive(Debug, Copy, Clone)]
+pub struct AverageColorizer {
+    pub distance: DistanceColorizer,
+    /// Weight of the average against the distance, in [0, 1].
+    pub mix: f64,
+}
+
+impl Colorizer for AverageColorizer {
+    fn color(&self, orbit: &Orbit, palette: &Palette) -> Srgb {
+        let distance = self.distance.position(orbit);
+        palette.pick_srgb((1.0 - self.mix) * distance + self.mix * orbit.average)
+    }
+}
+
+/// Squeeze values in range [0, infty) into [0, 1).
+#[inline]
+fn squeeze(x: f64) -> f64 {
+    f64::exp(-x)
+}
+
+/// Choice of [Colorizer], e.g. on the command line.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum Coloring {
+    /// See [DistanceColorizer].
+    #[default]
+    Distance,
+    /// See [IterationColorizer].
+    Iterations,
+    /// See [BlendColorizer], weighted by [Sampling::blend].
+    Blend,
+    /// See [TrapColorizer], trapping orbits in [Sampling::trap], shaded if [Sampling::shade].
+    Trap,
+    /// See [AverageColorizer], averaging [Average::Stripes] of frequency
+    /// [Sampling::stripes], mixed by [Sampling::mix].
+    Stripes,
+    /// See [AverageColorizer], averaging [Average::Curvature], mixed by [Sampling::mix].
+    Curvature,
+    /// See [DecompositionColorizer], split in two by binary decomposition,
+    /// with bands if [Sampling::bands].
+    Binary,
+    /// See [DecompositionColorizer], with field lines, and with bands if [Sampling::bands].
+    FieldLines,
+}
+
+impl Coloring {
+    pub const NAMES: [&str; 8] = [
+        "distance",
+        "iterations",
+        "blend",
+        "trap",
+        "stripes",
+        "curvature",
+        "binary",
+        "field-lines",
+    ];
+
+    /// The average to iterate orbits with, if any.
+    pub fn average(&self, sampling: &Sampling) -> Option<Average> {
+        match self {
+            Self::Stripes => Some(Average::Stripes {
+                frequency: sampling.stripes,
+            }),
+            Self::Curvature => Some(Average::Curvature),
+            _ => None,
+        }
+    }
+
+    /// The colorizer for a (dis)connected set, configured by `sampling`.
+    pub fn colorizer(&self, connected: bool, sampling: &Sampling) -> Box<dyn Colorizer> {
+        let distance = DistanceColorizer::new(connected);
+        match self {
+            Self::Distance => Box::new(distance),
+            Self::Iterations => Box::new(IterationColorizer),
+            Self::Blend => Box::new(BlendColorizer {
+                distance,
+                weight: sampling.blend,
+            }),
+            Self::Trap => Box::new(TrapColorizer {
+                shading: sampling.shade.then_some(distance),
+            }),
+            Self::Stripes | Self::Curvature => Box::new(AverageColorizer {
+                distance,
+                mix: sampling.mix,
+            }),
+            Self::Binary | Self::FieldLines => Box::new(DecompositionColorizer {
+                distance,
+                field_lines: *self == Self::FieldLines,
+                bands: sampling.bands,
+            }),
+        }
+    }
+}
+
+impl FromStr for Coloring {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "distance" => Ok(Self::Distance),
+            "iterations" => Ok(Self::Iterations),
+            "blend" => Ok(Self::Blend),
+            "trap" => Ok(Self::Trap),
+            "stripes" => Ok(Self::Stripes),
+            "curvature" => Ok(Self::Curvature),
+            "binary" => Ok(Self::Binary),
+            "field-lines" => Ok(Self::FieldLines),
+            _ => bail!(
+                "Unknown coloring {s:?}, expected one of {names}",
+                names = Self::NAMES.join(", ")
+            ),
+        }
+    }
+}
+
+impl Display for Coloring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Distance => "distance",
+            Self::Iterations => "iterations",
+            Self::Blend => "blend",
+            Self::Trap => "trap",
+            Self::Stripes => "stripes",
+            Self::Curvature => "curvature",
+            Self::Binary => "binary",
+            Self::FieldLines => "field-lines",
+        };
+        f.write_str(name)
+    }
+}
@@ -0,0 +1 @@
+pub type Complex<T = f64> = num_complex::Complex<T>;
@@ -0,0 +1,1463 @@
+use std::ops::Range;
+
+use cgmath::Zero;
+use rand::{Rng, RngExt};
+use rand_distr::{Distribution, Normal, Uniform};
+
+use crate::{
+    average::Average,
+    complex::Complex,
+    float::{Lanes, Real},
+    trap::Trap,
+};
+
+/// Longest attracting cycle searched for by [AttractingCycle::find].
+const MAX_PERIOD: usize = 1024;
+
+/// Candidate radii (around the first point of an attracting cycle) in which
+/// orbits are considered captured, largest first.
+const CAPTURE_RADII: [f64; 6] = [1e-2, 1e-3, 1e-4, 1e-6, 1e-8, 1e-10];
+
+/// Capture radii must exceed the rounding error of the iteration by this factor.
+const CAPTURE_MARGIN: f64 = 1e3;
+
+/// Bounds on the factors `4|z|²` multiplied into the derivative of an orbit
+/// while it follows a cycle.
+#[derive(Debug, Clone, Copy)]
+struct CycleBound {
+    period: usize,
+    /// Upper bound on the product of the factors over one period.
+    per_period: f64,
+    /// Upper bound on the product of the factors over any part of a period.
+    max_prefix: f64,
+}
+
+impl CycleBound {
+    /// Bound the factors of orbits that start within `radius` of `points[0]`.
+    ///
+    /// Returns `None` if such orbits are not guaranteed to stay within `radius`
+    /// after one period, or if the derivative does not shrink over a period.
+    fn new(points: &[Complex], radius: f64) -> Option<Self> {
+        let mut deviation = radius;
+        let mut per_period = 1.0;
+        let mut max_prefix: f64 = 1.0;
+
+        for z in points {
+            let norm = z.norm();
+            per_period *= 4.0 * (norm + deviation) * (norm + deviation);
+            max_prefix = max_prefix.max(per_period);
+            // |f(z + δ) - f(z)| = |2zδ + δ²| ≤ |δ| (2|z| + |δ|)
+            deviation *= 2.0 * norm + deviation;
+        }
+
+        (deviation <= radius && per_period < 1.0).then_some(Self {
+            period: points.len(),
+            per_period,
+            max_prefix,
+        })
+    }
+
+    /// Whether the derivative `diff` is guaranteed to drop below `epsilon`
+    /// once the orbit has followed the cycle for `remaining` more iterations.
+    fn vanishes(&self, diff: f64, remaining: usize, epsilon: f64) -> bool {
+        let periods = (remaining / self.period) as f64;
+        let bound = diff.ln() + periods * self.per_period.ln() + self.max_prefix.ln();
+
+        // Leave plenty of room for rounding errors in the actual iteration.
+        bound < (0.5 * epsilon).ln()
+    }
+}
+
+/// An attracting cycle of `f(z) = z² + c`.
+///
+/// If it exists, it attracts the orbit of the critical point and its basin is
+/// the interior of the (connected) Julia set.
+#[derive(Debug, Clone)]
+pub struct AttractingCycle {
+    pub points: Vec<Complex>,
+    /// Derivative of `f` composed `period` times, along the cycle.
+    pub multiplier: Complex,
+    capture_radius: f64,
+    bound: CycleBound,
+}
+
+impl AttractingCycle {
+    /// Find the attracting cycle by following the critical orbit until it
+    /// settles, then refining the cycle with Newton's method.
+    ///
+    /// Returns `None` if the critical orbit escapes, if it has not come close
+    /// to a cycle of period at most [MAX_PERIOD] after `max_iter` iterations,
+    /// or if the cycle attracts too weakly to bound orbits near it.
+    pub fn find(c: Complex, max_iter: usize) -> Option<Self> {
+        let f = |z: Complex| z * z + c;
+
+        let mut z: Complex = Complex::zero();
+        for _ in 0..max_iter {
+            z = f(z);
+            if z.norm_sqr() > 4.0 {
+                return None;
+            }
+        }
+
+        let mut w = z;
+        let period = (1..=MAX_PERIOD).find(|_| {
+            w = f(w);
+            (w - z).norm_sqr() < 1e-12
+        })?;
+
+        // Newton's method for g(z) = f^period(z) - z.
+        for _ in 0..16 {
+            let (mut w, mut dw) = (z, Complex::new(1.0, 0.0));
+            for _ in 0..period {
+                dw = 2.0 * w * dw;
+                w = f(w);
+            }
+            let step = (w - z) / (dw - Complex::new(1.0, 0.0));
+            if !step.is_finite() {
+                return None;
+            }
+            z -= step;
+            if step.norm_sqr() < 1e-30 {
+                break;
+            }
+        }
+
+        let points: Vec<Complex> = std::iter::successors(Some(z), |&z| Some(f(z)))
+            .take(period)
+            .collect();
+        let multiplier = points
+            .iter()
+            .fold(Complex::new(1.0, 0.0), |product, &z| 2.0 * z * product);
+        if multiplier.norm() >= 1.0 {
+            return None;
+        }
+
+        let (capture_radius, bound) = CAPTURE_RADII
+            .iter()
+            .find_map(|&radius| Some((radius, CycleBound::new(&points, radius)?)))?;
+
+        Some(Self {
+            points,
+            multiplier,
+            capture_radius,
+            bound,
+        })
+    }
+
+    pub fn period(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Radius around the points of the cycle within which orbits are captured by it.
+    pub fn capture_radius(&self) -> f64 {
+        self.capture_radius
+    }
+}
+
+/// The disk around the first point of an attracting cycle capturing orbits,
+/// in the precision the orbits are iterated in.
+#[derive(Debug, Clone, Copy)]
+struct Capture<T> {
+    center: Complex<T>,
+    radius_sqr: T,
+    bound: CycleBound,
+}
+
+impl<T: Real> Capture<T> {
+    /// Returns `None` if the capture radius of `cycle` is too close to the rounding
+    /// error of `T` for orbits to be trusted to stay within it.
+    fn new(cycle: &AttractingCycle) -> Option<Self> {
+        let center = cycle.points[0];
+        let radius = cycle.capture_radius;
+        let epsilon: f64 = T::epsilon().into();
+
+        (radius > CAPTURE_MARGIN * epsilon * center.norm().max(1.0)).then(|| Self {
+            center: Complex::new(T::from_f64(center.re), T::from_f64(center.im)),
+            radius_sqr: T::from_f64(radius * radius),
+            bound: cycle.bound,
+        })
+    }
+
+    fn captures(&self, z: Complex<T>) -> bool {
+        (z - self.center).norm_sqr() < self.radius_sqr
+    }
+}
+
+/// What iterating a point reveals about it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Orbit {
+    /// The last iterate `z_n`.
+    pub z: Complex,
+    /// Norm of the derivative of `z_n` with respect to the point.
+    pub derivative: f64,
+    /// Number of iterations `n`, until the orbit escaped, was recognized as
+    /// interior, or the iteration limit was reached.
+    pub iterations: usize,
+    pub escaped: bool,
+    /// Estimated distance to the Julia set, ≤ 0 for points in its interior.
+    pub distance: f64,
+    /// Smallest distance of the iterates `z_1, …, z_n` to the [Trap], if
+    /// [iterated with one](DistanceEstimation::with_trap), infinite otherwise.
+    pub trap: f64,
+    /// Smoothed [Average] along the orbit, if [iterated with
+    /// one](DistanceEstimation::with_average), 0 otherwise.
+    pub average: f64,
+    /// Direction (a complex number of norm 1) of the derivative of `z_n` with respect
+    /// to the point, if [tracked](DistanceEstimation::with_direction), 0 otherwise.
+    pub direction: Complex,
+    /// Norm of the derivative of the orbit where it entered the disk capturing it
+    /// (interpolated between the iterates before and after), if captured by an
+    /// attracting cycle and [tracked](DistanceEstimation::with_entry), 0 otherwise.
+    pub entry: f64,
+}
+
+impl Orbit {
+    /// Orbit after `iterations` iterations ending in `z`, with `diff` the derivative
+    /// of the squared norm of `z`, what was accumulated along the orbit in `tally`,
+    /// and the smoothed `average`.
+    fn new<T: Real>(
+        z: Complex<T>,
+        diff: T,
+        iterations: usize,
+        escaped: bool,
+        tally: &Tally<T>,
+        average: f64,
+    ) -> Self {
+        Self {
+            z: Complex::new(z.re.into(), z.im.into()),
+            derivative: Into::<f64>::into(diff).sqrt(),
+            iterations,
+            escaped,
+            distance: estimate(z.norm_sqr(), diff, escaped).into(),
+            trap: tally.trap.into(),
+            average,
+            direction: Complex::new(tally.direction.re.into(), tally.direction.im.into()),
+            entry: 0.0,
+        }
+    }
+
+    /// Orbit of a point recognized as interior after `iterations` iterations.
+    fn interior<T: Real>(
+        z: Complex<T>,
+        diff: T,
+        iterations: usize,
+        tally: &Tally<T>,
+        average: f64,
+    ) -> Self {
+        Self {
+            distance: 0.0,
+            ..Self::new(z, diff, iterations, false, tally, average)
+        }
+    }
+
+    /// Normalized continuous iteration count ν = n + 1 − log₂(log|z_n|), if the orbit escaped.
+    pub fn smooth_iterations(&self) -> Option<f64> {
+        let log_norm = self.z.norm().ln();
+        self.escaped
+            .then(|| self.iterations as f64 + 1.0 - log_norm.log2())
+    }
+}
+
+/// What orbits accumulate for coloring, in precision `T` or in lanes of it.
+#[derive(Debug, Clone, Copy)]
+struct Tally<T> {
+    /// Smallest distance of the iterates to the trap.
+    trap: T,
+    /// Sum of the terms of the [Average].
+    sum: T,
+    /// Last term of the [Average].
+    last: T,
+    /// Direction of the derivative.
+    direction: Complex<T>,
+}
+
+/// Estimates distances to the Julia set of `f(z) = z² + c`, iterating points in precision `T`.
+pub struct DistanceEstimation<T = f64> {
+    c: Complex<T>,
+    max_iter: usize,
+    connected: bool,
+    cycle: Option<AttractingCycle>,
+    capture: Option<Capture<T>>,
+    /// Whether to recognize interior points early, which never changes the estimates.
+    detect_interior: bool,
+    trap: Option<Trap<T>>,
+    average: Option<Average>,
+    direction: bool,
+    entry: bool,
+}
+
+impl<T: Real> DistanceEstimation<T> {
+    /// Properties of the parameter (connectedness, the attracting cycle) are
+    /// determined in double precision, whatever `T` is.
+    pub fn new(c: Complex, max_iter: usize) -> Self {
+        let cycle = AttractingCycle::find(c, max_iter);
+        let capture = cycle.as_ref().and_then(Capture::new);
+        Self {
+            c: Complex::new(T::from_f64(c.re), T::from_f64(c.im)),
+            max_iter,
+            connected: is_connected(c, max_iter),
+            cycle,
+            capture,
+            detect_interior: true,
+            trap: None,
+            average: None,
+            direction: false,
+            entry: false,
+        }
+    }
+
+    /// The same estimation, iterating points in precision `U`.
+    ///
+    /// Unlike [DistanceEstimation::new], this does not determine the properties
+    /// of the parameter again.
+    pub fn cast<U: Real>(&self) -> DistanceEstimation<U> {
+        DistanceEstimation {
+            c: Complex::new(U::from_f64(self.c.re.into()), U::from_f64(self.c.im.into())),
+            max_iter: self.max_iter,
+            connected: self.connected,
+            cycle: self.cycle.clone(),
+            capture: self.cycle.as_ref().and_then(Capture::new),
+            detect_interior: self.detect_interior,
+            trap: self.trap.as_ref().map(Trap::cast),
+            average: self.average,
+            direction: self.direction,
+            entry: self.entry,
+        }
+    }
+
+    /// Also measure the distances of orbits to `trap` (which takes time).
+    pub fn with_trap(self, trap: Trap) -> Self {
+        Self {
+            trap: Some(trap.cast()),
+            ..self
+        }
+    }
+
+    /// Also average `average` along orbits (which takes time).
+    pub fn with_average(self, average: Average) -> Self {
+        Self {
+            average: Some(average),
+            ..self
+        }
+    }
+
+    /// Also track the [direction](Orbit::direction) of the derivative of orbits.
+    pub fn with_direction(self) -> Self {
+        Self {
+            direction: true,
+            ..self
+        }
+    }
+
+    /// Also track the derivative of orbits where they [entered](Orbit::entry) the
+    /// disk capturing them (which iterates captured orbits a second time).
+    pub fn with_entry(self) -> Self {
+        Self {
+            entry: true,
+            ..self
+        }
+    }
+
+    pub fn attracting_cycle(&self) -> Option<&AttractingCycle> {
+        self.cycle.as_ref()
+    }
+
+    /// A Julia set is connected if and only if the orbit
+    /// of the critical point 0+0i is bounded.
+    ///
+    /// In other words: a Julia set is connected if c lies in
+    /// the Mandelbrot set.
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Iterate `z` until it escapes or is recognized as interior.
+    pub fn orbit(&self, mut z: Complex<T>) -> Orbit {
+        let start = z;
+        let mut entry = T::zero();
+
+        // Squared norm of iteraded point z.
+        let mut magnitude = z.norm_sqr();
+
+        // Derivative of the magnitude of z, squared.
+        let mut diff = T::one();
+
+        let four = T::from_f64(4.0);
+        let max_iter_f = T::from_f64(self.max_iter as f64);
+        let escape = max_iter_f * max_iter_f;
+
+        // Interior points never escape, but may be recognized early: Once the
+        // derivative is certain to vanish, the estimate below is 0 anyway.
+        // Orbits are either captured near the attracting cycle, or (if there
+        // is none) found to repeat exactly by Brent's cycle detection.
+        let mut check_interior = self.detect_interior;
+        let mut saved = z;
+        let mut saved_at = 0;
+
+        let mut tally = Tally {
+            trap: T::infinity(),
+            sum: T::zero(),
+            last: T::zero(),
+            direction: Complex::new(T::zero(), T::zero()),
+        };
+        if self.direction {
+            tally.direction.re = T::one();
+        }
+        let (mut previous, mut before) = (z, z);
+
+        for iter in 1..=self.max_iter {
+            diff = diff * (four * magnitude);
+            if self.direction {
+                // The derivative is multiplied by 2 z_n, so its direction by z_n;
+                // normalizing keeps it from overflowing.
+                let w = tally.direction;
+                let (re, im) = (z.re * w.re - z.im * w.im, z.re * w.im + z.im * w.re);
+                let norm = (re * re + im * im).sqrt();
+                tally.direction = Complex::new(re / norm, im / norm);
+            }
+            z = z * z + self.c;
+
+            magnitude = z.norm_sqr();
+
+            if let Some(shape) = &self.trap {
+                tally.trap = tally.trap.min(shape.distance(z));
+            }
+            if let Some(average) = &self.average {
+                // Terms start at z_3, so that they do not depend on z_0: points z and -z
+                // share all later iterates, and so the symmetry of the set is kept.
+                if iter >= 3 {
+                    tally.last = average.term(z, previous, before);
+                    tally.sum = tally.sum + tally.last;
+                }
+                (previous, before) = (z, previous);
+            }
+
+            if magnitude > escape {
+                let average = smooth_average(self.average, self.max_iter, z, iter, true, &tally);
+                let orbit = Orbit::new(z, diff, iter, true, &tally, average);
+                return Orbit {
+                    entry: entry.into(),
+                    ..orbit
+                };
+            }
+
+            if !check_interior {
+                continue;
+            }
+
+            let remaining = self.max_iter - iter;
+            let bound = match &self.capture {
+                Some(capture) if capture.captures(z) => {
+                    if self.entry {
+                        entry = self.entry(capture, start, z, diff, iter);
+                    }
+                    Some(capture.bound)
+                }
+                Some(_) => None,
+                None if z == saved => Some(self.exact_cycle_bound(z, iter - saved_at)),
+                None => {
+                    if iter.is_power_of_two() {
+                        saved = z;
+                        saved_at = iter;
+                    }
+                    None
+                }
+            };
+
+            if let Some(bound) = bound {
+                if bound.vanishes(diff.into(), remaining, T::epsilon().into()) {
+                    let average =
+                        smooth_average(self.average, self.max_iter, z, iter, false, &tally);
+                    let orbit = Orbit::interior(z, diff, iter, &tally, average);
+                    return Orbit {
+                        entry: entry.into(),
+                        ..orbit
+                    };
+                }
+                // The bound shrinks along with `diff`, so checking again is futile.
+                check_interior = false;
+            }
+        }
+
+        let average = smooth_average(self.average, self.max_iter, z, self.max_iter, false, &tally);
+        let orbit = Orbit::new(z, diff, self.max_iter, false, &tally, average);
+        Orbit {
+            entry: entry.into(),
+            ..orbit
+        }
+    }
+
+    /// Iterate all `points`, writing what their orbits reveal to `orbits`.
+    ///
+    /// Points are iterated in SIMD lanes, as many at a time as fit (see [Real::Lanes]);
+    /// the remainder falls back to [DistanceEstimation::orbit]. Every lane performs the
+    /// same floating point operations in the same order as the scalar function (Rust never
+    /// fuses them), so the results are identical: the tolerance is zero ulp, not just
+    /// rounding noise.
+    pub fn orbits(&self, points: &[Complex<T>], orbits: &mut [Orbit]) {
+        assert_eq!(points.len(), orbits.len());
+
+        let lanes = <T::Lanes as Lanes<T>>::LANES;
+        let mut points_chunks = points.chunks_exact(lanes);
+        let mut orbits_chunks = orbits.chunks_exact_mut(lanes);
+        for (points, orbits) in (&mut points_chunks).zip(&mut orbits_chunks) {
+            self.orbit_lanes(points, orbits);
+        }
+
+        let remainder = points_chunks.remainder().iter();
+        for (&point, orbit) in remainder.zip(orbits_chunks.into_remainder()) {
+            *orbit = self.orbit(point);
+        }
+    }
+
+    /// Like [DistanceEstimation::orbit], for one full vector of points at once.
+    ///
+    /// Once a lane escapes or is recognized as interior, its estimate is taken and
+    /// the lane is filled with NaN, which no check ever matches again. Unlike freezing
+    /// its values, this keeps vanishing derivatives from slowing down the remaining
+    /// iterations with subnormal numbers.
+    fn orbit_lanes(&self, points: &[Complex<T>], orbits: &mut [Orbit]) {
+        let splat = <T::Lanes as Lanes<T>>::splat;
+
+        let mut re = gather(points, |z| z.re);
+        let mut im = gather(points, |z| z.im);
+        let mut magnitude = re * re + im * im;
+        let mut diff = splat(T::one());
+
+        let (c_re, c_im) = (splat(self.c.re), splat(self.c.im));
+        let four = splat(T::from_f64(4.0));
+        let max_iter_f = T::from_f64(self.max_iter as f64);
+        let escape = splat(max_iter_f * max_iter_f);
+
+        // Bit masks of lanes, like those returned by `move_mask`.
+        let all = (1 << points.len()) - 1;
+        let mut done = 0;
+        let mut checking = if self.detect_interior { all } else { 0 };
+        let (mut saved_re, mut saved_im) = (re, im);
+        let mut saved_at = 0;
+
+        let mut tally = Tally {
+            trap: splat(T::infinity()),
+            sum: splat(T::zero()),
+            last: splat(T::zero()),
+            direction: Complex::new(splat(T::zero()), splat(T::zero())),
+        };
+        if self.direction {
+            tally.direction.re = splat(T::one());
+        }
+        let (mut previous, mut before) = ((re, im), (re, im));
+        let mut entries = <T::Lanes as Lanes<T>>::Array::default();
+
+        // Take the orbits of the lanes in `retire` after `iterations` iterations,
+        // and fill them with NaN. Lanes are either interior, or escaped, or neither.
+        let mut finish = |retire: i32,
+                          interior: bool,
+                          escaped: bool,
+                          iterations: usize,
+                          re: &mut T::Lanes,
+                          im: &mut T::Lanes,
+                          diff: T::Lanes,
+                          tally: &Tally<T::Lanes>,
+                          entries: &<T::Lanes as Lanes<T>>::Array| {
+            let (re_lanes, im_lanes, diff) = (re.to_array(), im.to_array(), diff.to_array());
+            let (trap, sum, last) = (
+                tally.trap.to_array(),
+                tally.sum.to_array(),
+                tally.last.to_array(),
+            );
+            let direction = (tally.direction.re.to_array(), tally.direction.im.to_array());
+            for lane in lanes_in(retire, points.len()) {
+                let z = Complex::new(re_lanes.as_ref()[lane], im_lanes.as_ref()[lane]);
+                let diff = diff.as_ref()[lane];
+                let tally = Tally {
+                    trap: trap.as_ref()[lane],
+                    sum: sum.as_ref()[lane],
+                    last: last.as_ref()[lane],
+                    direction: Complex::new(direction.0.as_ref()[lane], direction.1.as_ref()[lane]),
+                };
+                let average =
+                    smooth_average(self.average, self.max_iter, z, iterations, escaped, &tally);
+                let orbit = if interior {
+                    Orbit::interior(z, diff, iterations, &tally, average)
+                } else {
+                    Orbit::new(z, diff, iterations, escaped, &tally, average)
+                };
+                orbits[lane] = Orbit {
+                    entry: entries.as_ref()[lane].into(),
+                    ..orbit
+                };
+            }
+
+            let retired = lane_mask::<T>(retire, points.len());
+            *re = retired.blend(splat(T::nan()), *re);
+            *im = retired.blend(splat(T::nan()), *im);
+        };
+
+        for iter in 1..=self.max_iter {
+            diff = diff * (four * magnitude);
+            if self.direction {
+                let w = tally.direction;
+                let (w_re, w_im) = (re * w.re - im * w.im, re * w.im + im * w.re);
+                let norm = (w_re * w_re + w_im * w_im).sqrt();
+                tally.direction = Complex::new(w_re / norm, w_im / norm);
+            }
+            (re, im) = ((re * re - im * im) + c_re, (re * im + im * re) + c_im);
+            magnitude = re * re + im * im;
+
+            if let Some(shape) = &self.trap {
+                tally.trap = tally.trap.min(shape.distance_lanes(re, im));
+            }
+            if let Some(average) = &self.average {
+                if iter >= 3 {
+                    tally.last = average.term_lanes::<T>((re, im), previous, before);
+                    tally.sum = tally.sum + tally.last;
+                }
+                (previous, before) = ((re, im), previous);
+            }
+
+            let escaped = magnitude.cmp_gt(escape).move_mask();
+            if escaped != 0 {
+                finish(
+                    escaped, false, true, iter, &mut re, &mut im, diff, &tally, &entries,
+                );
+                done |= escaped;
+                if done == all {
+                    return;
+                }
+            }
+
+            // Interior checks as in the scalar function, but only lanes that
+            // meet a bound leave the vector code.
+            if checking & !done == 0 {
+                continue;
+            }
+
+            let remaining = self.max_iter - iter;
+            let mut period = 0;
+            let found = match &self.capture {
+                Some(capture) => {
+                    let d_re = re - splat(capture.center.re);
+                    let d_im = im - splat(capture.center.im);
+                    (d_re * d_re + d_im * d_im).cmp_lt(splat(capture.radius_sqr))
+                }
+                None => {
+                    let repeated = re.cmp_eq(saved_re) & im.cmp_eq(saved_im);
+                    period = iter - saved_at;
+                    if iter.is_power_of_two() {
+                        (saved_re, saved_im) = (re, im);
+                        saved_at = iter;
+                    }
+                    repeated
+                }
+            };
+            let found = found.move_mask() & checking;
+            if found == 0 {
+                continue;
+            }
+
+            let (diff_lanes, re_lanes, im_lanes) = (diff.to_array(), re.to_array(), im.to_array());
+            let mut interior = 0;
+            for lane in lanes_in(found, points.len()) {
+                let bound = match &self.capture {
+                    Some(capture) => {
+                        if self.entry {
+                            let z = Complex::new(re_lanes.as_ref()[lane], im_lanes.as_ref()[lane]);
+                            let diff = diff_lanes.as_ref()[lane];
+                            entries.as_mut()[lane] =
+                                self.entry(capture, points[lane], z, diff, iter);
+                        }
+                        capture.bound
+                    }
+                    None => {
+                        let z = Complex::new(re_lanes.as_ref()[lane], im_lanes.as_ref()[lane]);
+                        self.exact_cycle_bound(z, period)
+                    }
+                };
+                let diff = diff_lanes.as_ref()[lane];
+                if bound.vanishes(diff.into(), remaining, T::epsilon().into()) {
+                    interior |= 1 << lane;
+                }
+            }
+            // The bound shrinks along with `diff`, so checking again is futile.
+            checking &= !found;
+
+            if interior != 0 {
+                finish(
+                    interior, true, false, iter, &mut re, &mut im, diff, &tally, &entries,
+                );
+                done |= interior;
+                if done == all {
+                    return;
+                }
+            }
+        }
+
+        finish(
+            all & !done,
+            false,
+            false,
+            self.max_iter,
+            &mut re,
+            &mut im,
+            diff,
+            &tally,
+            &entries,
+        );
+    }
+
+    /// Norm of the derivative of the orbit of `start` where it entered the disk of
+    /// `capture`, if it was captured at iteration `iterations` ending in `z`, with `diff`
+    /// the derivative of the squared norm of `z`.
+    ///
+    /// Between the iterate one period before and `z`, both the distance to the center of
+    /// the disk and the derivative shrink by `|λ|` as long as the cycle is linearizable,
+    /// so interpolating the logarithm of the derivative in the logarithm of the distance
+    /// does not depend on the period the orbit is captured in. It also stays bounded
+    /// where orbits land right on the cycle, unlike extrapolating from `z` alone.
+    fn entry(
+        &self,
+        capture: &Capture<T>,
+        start: Complex<T>,
+        z: Complex<T>,
+        diff: T,
+        iterations: usize,
+    ) -> T {
+        let derivative: f64 = diff.sqrt().into();
+        let Some(before) = iterations.checked_sub(capture.bound.period) else {
+            return T::from_f64(derivative);
+        };
+
+        // Iterate again up to the iterate one period before.
+        let four = T::from_f64(4.0);
+        let (mut w, mut diff_before) = (start, T::one());
+        for _ in 0..before {
+            diff_before = diff_before * (four * w.norm_sqr());
+            w = w * w + self.c;
+        }
+
+        let radius_sqr: f64 = capture.radius_sqr.into();
+        let outside: f64 = (w - capture.center).norm_sqr().into();
+        let inside: f64 = (z - capture.center).norm_sqr().into();
+        let derivative_before = Into::<f64>::into(diff_before).sqrt();
+        if outside <= radius_sqr {
+            // Only the starting point is not checked for being captured.
+            return T::from_f64(derivative);
+        }
+
+        let fraction = (outside / radius_sqr).ln() / (outside / inside).ln();
+        T::from_f64(derivative_before * (derivative / derivative_before).powf(fraction))
+    }
+
+    /// Bound the factors of an orbit repeating exactly with the given period.
+    fn exact_cycle_bound(&self, z: Complex<T>, period: usize) -> CycleBound {
+        let mut per_period = 1.0;
+        let mut max_prefix: f64 = 1.0;
+        let mut w = z;
+
+        for _ in 0..period {
+            let norm_sqr: f64 = w.norm_sqr().into();
+            per_period *= 4.0 * norm_sqr;
+            max_prefix = max_prefix.max(per_period);
+            w = w * w + self.c;
+        }
+
+        CycleBound {
+            period,
+            per_period,
+            max_prefix,
+        }
+    }
+}
+
+/// The `average` of an orbit ending in `z` after `iterations` iterations, blended by
+/// how far it escaped past `max_iter²` (Härkönen's fractional iteration count), 0 without one.
+fn smooth_average<T: Real>(
+    average: Option<Average>,
+    max_iter: usize,
+    z: Complex<T>,
+    iterations: usize,
+    escaped: bool,
+    tally: &Tally<T>,
+) -> f64 {
+    if average.is_none() {
+        return 0.0;
+    }
+    let weight = if escaped {
+        // 1 if |z_n|² just passed the escape radius R², 0 if it passed R⁴
+        // (and so z_{n-1} only just missed escaping).
+        let escape = 2.0 * (max_iter as f64).ln();
+        let magnitude: f64 = z.norm_sqr().into();
+        (1.0 + (escape / magnitude.ln()).log2()).clamp(0.0, 1.0)
+    } else {
+        1.0
+    };
+    let terms = iterations.saturating_sub(2);
+    Average::smooth(tally.sum.into(), tally.last.into(), terms, weight)
+}
+
+/// Estimates distances to the Mandelbrot set, iterating `z² + c` from `z = 0`
+/// for every point `c` in precision `T`.
+///
+/// Unlike [DistanceEstimation], there is no attracting cycle common to all points to
+/// recognize the interior by early. Only points in the main cardioid are known to be
+/// interior right away; other orbits that do not escape are iterated up to the limit,
+/// and count as interior (distance 0) then.
+pub struct MandelbrotEstimation<T = f64> {
+    max_iter: usize,
+    trap: Option<Trap<T>>,
+    average: Option<Average>,
+    direction: bool,
+}
+
+impl<T: Real> MandelbrotEstimation<T> {
+    pub fn new(max_iter: usize) -> Self {
+        Self {
+            max_iter,
+            trap: None,
+            average: None,
+            direction: false,
+        }
+    }
+
+    /// The same estimation, iterating points in precision `U`.
+    pub fn cast<U: Real>(&self) -> MandelbrotEstimation<U> {
+        MandelbrotEstimation {
+            max_iter: self.max_iter,
+            trap: self.trap.as_ref().map(Trap::cast),
+            average: self.average,
+            direction: self.direction,
+        }
+    }
+
+    /// Also measure the distances of orbits to `trap` (which takes time).
+    pub fn with_trap(self, trap: Trap) -> Self {
+        Self {
+            trap: Some(trap.cast()),
+            ..self
+        }
+    }
+
+    /// Also average `average` along orbits (which takes time).
+    pub fn with_average(self, average: Average) -> Self {
+        Self {
+            average: Some(average),
+            ..self
+        }
+    }
+
+    /// Also track the [direction](Orbit::direction) of the derivative of escaping orbits.
+    pub fn with_direction(self) -> Self {
+        Self {
+            direction: true,
+            ..self
+        }
+    }
+
+    /// Iterate the critical point 0 for the parameter `c` until it escapes.
+    pub fn orbit(&self, c: Complex<T>) -> Orbit {
+        let (zero, one, two) = (T::zero(), T::one(), T::from_f64(2.0));
+        let mut z = Complex::new(zero, zero);
+        // Derivative of z with respect to c.
+        let mut dz = z;
+
+        let mut tally = Tally {
+            trap: T::infinity(),
+            sum: zero,
+            last: zero,
+            direction: Complex::new(zero, zero),
+        };
+        if in_main_cardioid(c) {
+            return self.finish(z, dz, 0, false, &mut tally);
+        }
+
+        let max_iter_f = T::from_f64(self.max_iter as f64);
+        let escape = max_iter_f * max_iter_f;
+        let (mut previous, mut before) = (z, z);
+
+        for iter in 1..=self.max_iter {
+            dz = Complex::new(
+                two * (z.re * dz.re - z.im * dz.im) + one,
+                two * (z.re * dz.im + z.im * dz.re),
+            );
+            z = z * z + c;
+
+            if let Some(shape) = &self.trap {
+                tally.trap = tally.trap.min(shape.distance(z));
+            }
+            if let Some(average) = &self.average {
+                // As for Julia sets, terms start at z_3: z_0 = 0 has no argument.
+                if iter >= 3 {
+                    tally.last = average.term(z, previous, before);
+                    tally.sum = tally.sum + tally.last;
+                }
+                (previous, before) = (z, previous);
+            }
+
+            if z.norm_sqr() > escape {
+                return self.finish(z, dz, iter, true, &mut tally);
+            }
+        }
+
+        self.finish(z, dz, self.max_iter, false, &mut tally)
+    }
+
+    /// The orbit ending in `z` with derivative `dz` after `iterations` iterations.
+    fn finish(
+        &self,
+        z: Complex<T>,
+        dz: Complex<T>,
+        iterations: usize,
+        escaped: bool,
+        tally: &mut Tally<T>,
+    ) -> Orbit {
+        let average = smooth_average(self.average, self.max_iter, z, iterations, escaped, tally);
+        if !escaped {
+            return Orbit::interior(z, dz.norm_sqr(), iterations, tally, average);
+        }
+        if self.direction {
+            tally.direction = direction(dz);
+        }
+        Orbit::new(z, dz.norm_sqr(), iterations, true, tally, average)
+    }
+
+    /// Iterate all `points`, like [DistanceEstimation::orbits] (and with the same results
+    /// as [MandelbrotEstimation::orbit]).
+    pub fn orbits(&self, points: &[Complex<T>], orbits: &mut [Orbit]) {
+        assert_eq!(points.len(), orbits.len());
+
+        let lanes = <T::Lanes as Lanes<T>>::LANES;
+        let mut points_chunks = points.chunks_exact(lanes);
+        let mut orbits_chunks = orbits.chunks_exact_mut(lanes);
+        for (points, orbits) in (&mut points_chunks).zip(&mut orbits_chunks) {
+            self.orbit_lanes(points, orbits);
+        }
+
+        let remainder = points_chunks.remainder().iter();
+        for (&point, orbit) in remainder.zip(orbits_chunks.into_remainder()) {
+            *orbit = self.orbit(point);
+        }
+    }
+
+    /// Like [MandelbrotEstimation::orbit], for one full vector of points at once.
+    ///
+    /// Finished lanes are filled with NaN, as in [DistanceEstimation::orbits].
+    fn orbit_lanes(&self, points: &[Complex<T>], orbits: &mut [Orbit]) {
+        let splat = <T::Lanes as Lanes<T>>::splat;
+
+        let c_re = gather(points, |c| c.re);
+        let c_im = gather(points, |c| c.im);
+        let (mut re, mut im) = (splat(T::zero()), splat(T::zero()));
+        let (mut dz_re, mut dz_im) = (re, im);
+
+        let (one, two) = (splat(T::one()), splat(T::from_f64(2.0)));
+        let max_iter_f = T::from_f64(self.max_iter as f64);
+        let escape = splat(max_iter_f * max_iter_f);
+
+        let all = (1 << points.len()) - 1;
+
+        let mut tally = Tally {
+            trap: splat(T::infinity()),
+            sum: splat(T::zero()),
+            last: splat(T::zero()),
+            direction: Complex::new(splat(T::zero()), splat(T::zero())),
+        };
+        let (mut previous, mut before) = ((re, im), (re, im));
+
+        // Take the orbits of the lanes in `retire` after `iterations` iterations,
+        // and fill them with NaN.
+        let mut finish = |retire: i32,
+                          escaped: bool,
+                          iterations: usize,
+                          re: &mut T::Lanes,
+                          im: &mut T::Lanes,
+                          (dz_re, dz_im): (T::Lanes, T::Lanes),
+                          tally: &Tally<T::Lanes>| {
+            let (re_lanes, im_lanes) = (re.to_array(), im.to_array());
+            let (dz_re, dz_im) = (dz_re.to_array(), dz_im.to_array());
+            let (trap, sum, last) = (
+                tally.trap.to_array(),
+                tally.sum.to_array(),
+                tally.last.to_array(),
+            );
+            for lane in lanes_in(retire, points.len()) {
+                let z = Complex::new(re_lanes.as_ref()[lane], im_lanes.as_ref()[lane]);
+                let dz = Complex::new(dz_re.as_ref()[lane], dz_im.as_ref()[lane]);
+                let mut tally = Tally {
+                    trap: trap.as_ref()[lane],
+                    sum: sum.as_ref()[lane],
+                    last: last.as_ref()[lane],
+                    direction: Complex::new(T::zero(), T::zero()),
+                };
+                orbits[lane] = self.finish(z, dz, iterations, escaped, &mut tally);
+            }
+
+            let retired = lane_mask::<T>(retire, points.len());
+            *re = retired.blend(splat(T::nan()), *re);
+            *im = retired.blend(splat(T::nan()), *im);
+        };
+
+        let mut done = in_main_cardioid_lanes::<T>(c_re, c_im).move_mask() & all;
+        if done != 0 {
+            finish(done, false, 0, &mut re, &mut im, (dz_re, dz_im), &tally);
+            if done == all {
+                return;
+            }
+        }
+
+        for iter in 1..=self.max_iter {
+            (dz_re, dz_im) = (
+                two * (re * dz_re - im * dz_im) + one,
+                two * (re * dz_im + im * dz_re),
+            );
+            (re, im) = ((re * re - im * im) + c_re, (re * im + im * re) + c_im);
+
+            if let Some(shape) = &self.trap {
+                tally.trap = tally.trap.min(shape.distance_lanes(re, im));
+            }
+            if let Some(average) = &self.average {
+                if iter >= 3 {
+                    tally.last = average.term_lanes::<T>((re, im), previous, before);
+                    tally.sum = tally.sum + tally.last;
+                }
+                (previous, before) = ((re, im), previous);
+            }
+
+            let escaped = (re * re + im * im).cmp_gt(escape).move_mask();
+            if escaped != 0 {
+                finish(
+                    escaped,
+                    true,
+                    iter,
+                    &mut re,
+                    &mut im,
+                    (dz_re, dz_im),
+                    &tally,
+                );
+                done |= escaped;
+                if done == all {
+                    return;
+                }
+            }
+        }
+
+        finish(
+            all & !done,
+            false,
+            self.max_iter,
+            &mut re,
+            &mut im,
+            (dz_re, dz_im),
+            &tally,
+        );
+    }
+}
+
+/// Whether `c` lies in the main cardioid of the Mandelbrot set, whose points are all interior.
+fn in_main_cardioid<T: Real>(c: Complex<T>) -> bool {
+    let mag = c.re * c.re + c.im * c.im;
+    let cardioid = T::from_f64(256.0) * mag * mag - T::from_f64(96.0) * mag
+        + T::from_f64(32.0) * c.re
+        - T::from_f64(3.0);
+    cardioid < T::zero()
+}
+
+/// Like [in_main_cardioid], for the points with real parts `re` and imaginary parts
+/// `im`, performing the same operations. Returns a vector mask.
+fn in_main_cardioid_lanes<T: Real>(re: T::Lanes, im: T::Lanes) -> T::Lanes {
+    let splat = <T::Lanes as Lanes<T>>::splat;
+    let mag = re * re + im * im;
+    let cardioid = splat(T::from_f64(256.0)) * mag * mag - splat(T::from_f64(96.0)) * mag
+        + splat(T::from_f64(32.0)) * re
+        - splat(T::from_f64(3.0));
+    cardioid.cmp_lt(splat(T::zero()))
+}
+
+/// The direction (of norm 1) of `dz`, scaled first to keep the norm from overflowing.
+fn direction<T: Real>(dz: Complex<T>) -> Complex<T> {
+    let scale = dz.re.abs().max(dz.im.abs());
+    if scale.is_infinite() {
+        return Complex::new(T::zero(), T::zero());
+    }
+    let (re, im) = (dz.re / scale, dz.im / scale);
+    let norm = (re * re + im * im).sqrt();
+    Complex::new(re / norm, im / norm)
+}
+
+/// A Julia set is connected if and only if the orbit
+/// of the critical point 0+0i is bounded.
+fn is_connected(c: Complex, max_iter: usize) -> bool {
+    let mut z: Complex = Complex::zero();
+
+    for _ in 0..max_iter {
+        z = z * z + c;
+        if z.norm_sqr() > 4.0 {
+            return false;
+        }
+    }
+    true
+}
+
+/// Estimate the distance of a point to the Julia set from the squared norm
+/// `magnitude` of its last iterate, the derivative `diff` of that norm, and
+/// whether its orbit `escaped`.
+fn estimate<T: Real>(magnitude: T, diff: T, escaped: bool) -> T {
+    // Resolve singularities of the distance estimation:
+    // d(magnitude) = sqrt(magnitude/magnitude') * ln(magnitude)
+    //
+    // d(magnitude) → 0 as magnitude → 0
+    if diff < T::epsilon() || magnitude < T::epsilon() {
+        return T::zero();
+    }
+    // Escaping orbits grow the derivative beyond the range of `T` (single precision
+    // more often), but their distance is positive nonetheless: the point is outside.
+    if escaped && diff.is_infinite() {
+        return T::min_positive_value();
+    }
+
+    (magnitude / diff).sqrt() * T::from_f64(0.5) * magnitude.ln()
+}
+
+/// A vector of one part of each of `points`, which fill all lanes.
+fn gather<T: Real>(points: &[Complex<T>], part: impl Fn(&Complex<T>) -> T) -> T::Lanes {
+    let mut array = <T::Lanes as Lanes<T>>::Array::default();
+    for (lane, point) in array.as_mut().iter_mut().zip(points) {
+        *lane = part(point);
+    }
+    T::Lanes::from_array(array)
+}
+
+/// Indices of the lanes set in the bit mask `lanes`, out of `count` lanes.
+fn lanes_in(lanes: i32, count: usize) -> impl Iterator<Item = usize> {
+    (0..count).filter(move |&lane| lanes & (1 << lane) != 0)
+}
+
+/// A vector mask selecting the lanes set in the bit mask `lanes`, out of `count` lanes.
+fn lane_mask<T: Real>(lanes: i32, count: usize) -> T::Lanes {
+    let mut selected = <T::Lanes as Lanes<T>>::Array::default();
+    for lane in lanes_in(lanes, count) {
+        selected.as_mut()[lane] = T::one();
+    }
+    T::Lanes::from_array(selected).cmp_eq(T::Lanes::splat(T::one()))
+}
+
+pub struct MandelbrotBoundary {
+    pub max_iter: usize,
+}
+
+impl MandelbrotBoundary {
+    fn escape(&self) -> f64 {
+        (self.max_iter as f64) * (self.max_iter as f64)
+    }
+
+    fn distance(&self, c: Complex) -> f64 {
+        let mag = c.norm_sqr();
+        if 256.0 * mag * mag - 96.0 * mag + 32.0 * c.re - 3.0 < 0.0 {
+            return 0.0;
+        }
+
+        let mut z: Complex = Complex::zero();
+        let mut dz: Complex = Complex::zero();
+
+        let escape = self.escape();
+
+        for _ in 0..self.max_iter {
+            dz = 2.0 * z * dz + 1.0;
+            z = z * z + c;
+
+            let mag = z.norm_sqr();
+            if mag > escape {
+                let dmag = dz.norm_sqr();
+                return 0.5 * (mag / dmag).sqrt() * mag.ln();
+            }
+        }
+
+        0.0
+    }
+}
+
+struct BoundingBoxDistribution {
+    real: Uniform<f64>,
+    imag: Uniform<f64>,
+}
+
+impl Distribution<Complex> for BoundingBoxDistribution {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Complex {
+        let real = rng.sample(self.real);
+        let imag = rng.sample(self.imag);
+        Complex::new(real, imag)
+    }
+}
+
+impl BoundingBoxDistribution {
+    fn new(real: Range<f64>, imag: Range<f64>) -> Self {
+        Self {
+            real: Uniform::new(real.start, real.end).unwrap(),
+            imag: Uniform::new(imag.start, imag.end).unwrap(),
+        }
+    }
+}
+
+impl Distribution<Complex> for MandelbrotBoundary {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Complex {
+        const DISTANCE: f64 = 1e-3;
+
+        let bbx_dist = BoundingBoxDistribution::new(-2.0..0.5, -1.2..1.2);
+        let c_preferred = rng
+            .sample_iter(bbx_dist)
+            .find(|&c| {
+                let dist = self.distance(c);
+                0.0 < dist && dist < DISTANCE
+            })
+            .unwrap();
+
+        let r: f64 = rng.sample(Normal::new(0.0, 40.0 * DISTANCE).unwrap());
+        let theta = rng.random_range(0.0..std::f64::consts::TAU);
+        let pertubation = Complex::from_polar(r, theta);
+
+        c_preferred + pertubation
+    }
+}
+
+/// Like [MandelbrotBoundary], but only samples parameters whose Julia set is
+/// connected (if `connected` is true) or disconnected (otherwise).
+pub struct ConnectedBoundary {
+    pub max_iter: usize,
+    pub connected: bool,
+}
+
+impl Distribution<Complex> for ConnectedBoundary {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Complex {
+        let boundary = MandelbrotBoundary {
+            max_iter: self.max_iter,
+        };
+
+        rng.sample_iter(boundary)
+            .find(|&c| is_connected(c, self.max_iter) == self.connected)
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points of a `width`×`height` grid covering the square of side 3.2 around 0.
+    fn grid<T: Real>(width: usize, height: usize) -> Vec<Complex<T>> {
+        let step = 3.2 / width.max(height) as f64;
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let re = (x as f64 - 0.5 * width as f64) * step;
+                let im = (y as f64 - 0.5 * height as f64) * step;
+                Complex::new(T::from_f64(re), T::from_f64(im))
+            })
+            .collect()
+    }
+
+    /// Connected parameters: the basilica, the rabbit, and one close to the parabolic
+    /// point 1/4, whose cycle attracts so weakly that orbits take long to be captured.
+    const CONNECTED: [Complex; 3] = [
+        Complex::new(-1.0, 0.0),
+        Complex::new(-0.123, 0.745),
+        Complex::new(0.24, 0.0),
+    ];
+
+    fn interior_detection_keeps_estimates<T: Real>() {
+        let points = grid::<T>(67, 50);
+        for c in CONNECTED {
+            let detecting = DistanceEstimation::<T>::new(c, 2048);
+            assert!(detecting.capture.is_some(), "no capture for c = {c}");
+            let iterating = DistanceEstimation {
+                detect_interior: false,
+                ..DistanceEstimation::<T>::new(c, 2048)
+            };
+
+            let mut detected = vec![Orbit::default(); points.len()];
+            let mut iterated = vec![Orbit::default(); points.len()];
+            detecting.orbits(&points, &mut detected);
+            iterating.orbits(&points, &mut iterated);
+
+            let mut interior = 0;
+            for (point, (detected, iterated)) in points.iter().zip(detected.iter().zip(&iterated)) {
+                assert_eq!(
+                    detected.distance.to_bits(),
+                    iterated.distance.to_bits(),
+                    "c = {c}, z = {point:?}"
+                );
+                interior += usize::from(detected.iterations < iterated.iterations);
+            }
+            assert!(interior > 0, "no interior point detected for c = {c}");
+        }
+    }
+
+    /// Bits of every field of `orbit`, so that NaN compares equal to itself.
+    fn bits(orbit: &Orbit) -> [u64; 11] {
+        [
+            orbit.z.re.to_bits(),
+            orbit.z.im.to_bits(),
+            orbit.derivative.to_bits(),
+            orbit.iterations as u64,
+            u64::from(orbit.escaped),
+            orbit.distance.to_bits(),
+            orbit.trap.to_bits(),
+            orbit.average.to_bits(),
+            orbit.direction.re.to_bits(),
+            orbit.direction.im.to_bits(),
+            orbit.entry.to_bits(),
+        ]
+    }
+
+    fn lanes_match_scalar<T: Real>() {
+        // Not a multiple of any number of lanes, so that the remainder is iterated too.
+        let points = grid::<T>(67, 50);
+        assert_ne!(points.len() % <T::Lanes as Lanes<T>>::LANES, 0);
+
+        let estimations = [
+            // Few iterations leave some interior points neither captured nor escaped.
+            (CONNECTED[0], 64, Trap::default(), Average::Curvature, true),
+            (
+                CONNECTED[1],
+                1024,
+                Trap::Line {
+                    point: Complex::new(0.1, -0.2),
+                    direction: Complex::from_polar(1.0, 0.5),
+                },
+                Average::Stripes { frequency: 5 },
+                true,
+            ),
+            (
+                CONNECTED[2],
+                1024,
+                Trap::Cross {
+                    center: Complex::new(-0.3, 0.2),
+                },
+                Average::Stripes { frequency: 2 },
+                true,
+            ),
+            // Without the attracting cycle, interior points are only recognized
+            // once their orbits repeat exactly.
+            (
+                CONNECTED[0],
+                1024,
+                Trap::Circle {
+                    center: Complex::new(0.2, 0.1),
+                    radius: 0.5,
+                },
+                Average::Curvature,
+                false,
+            ),
+            // Disconnected, with orbits escaping slowly near the parabolic point -3/4.
+            (
+                Complex::new(-0.75, 0.01),
+                1024,
+                Trap::default(),
+                Average::Stripes { frequency: 7 },
+                true,
+            ),
+        ];
+
+        let (mut escaped, mut interior, mut exhausted) = (0, 0, 0);
+        for (c, max_iter, trap, average, cycle) in estimations {
+            let mut estimation = DistanceEstimation::<T>::new(c, max_iter)
+                .with_trap(trap)
+                .with_average(average)
+                .with_direction()
+                .with_entry();
+            if !cycle {
+                estimation.cycle = None;
+                estimation.capture = None;
+            }
+
+            let mut orbits = vec![Orbit::default(); points.len()];
+            estimation.orbits(&points, &mut orbits);
+            let mut repeated = 0;
+            for (&point, orbit) in points.iter().zip(&orbits) {
+                let scalar = estimation.orbit(point);
+                assert_eq!(bits(orbit), bits(&scalar), "c = {c}, z = {point:?}");
+
+                if orbit.escaped {
+                    escaped += 1;
+                } else if orbit.iterations < max_iter {
+                    // Captured orbits entered the capture disk with some finite derivative.
+                    let entry = orbit.entry;
+                    assert!(
+                        !cycle || (entry > 0.0 && entry.is_finite()),
+                        "z = {point:?}"
+                    );
+                    interior += 1;
+                    repeated += usize::from(!cycle);
+                } else {
+                    exhausted += 1;
+                }
+            }
+            assert!(cycle || repeated > 0, "no orbit repeated for c = {c}");
+        }
+        assert!(escaped > 0 && interior > 0 && exhausted > 0);
+    }
+
+    fn mandelbrot_lanes_match_scalar<T: Real>() {
+        let points = grid::<T>(67, 50);
+        let estimations = [
+            MandelbrotEstimation::<T>::new(64).with_average(Average::Curvature),
+            MandelbrotEstimation::<T>::new(1024)
+                .with_trap(Trap::Cross {
+                    center: Complex::new(-0.3, 0.2),
+                })
+                .with_average(Average::Stripes { frequency: 5 })
+                .with_direction(),
+        ];
+
+        let (mut escaped, mut interior, mut cardioid) = (0, 0, 0);
+        for estimation in estimations {
+            let mut orbits = vec![Orbit::default(); points.len()];
+            estimation.orbits(&points, &mut orbits);
+            for (&point, orbit) in points.iter().zip(&orbits) {
+                let scalar = estimation.orbit(point);
+                assert_eq!(bits(orbit), bits(&scalar), "c = {point:?}");
+
+                if orbit.escaped {
+                    assert!(orbit.distance > 0.0, "c = {point:?}");
+                    // Terms are in [0, 1] up to rounding.
+                    let average = orbit.average;
+                    assert!((-1e-6..=1.0 + 1e-6).contains(&average), "c = {point:?}");
+                    escaped += 1;
+                } else {
+                    assert_eq!(orbit.distance, 0.0, "c = {point:?}");
+                    interior += 1;
+                    cardioid += usize::from(orbit.iterations == 0);
+                }
+            }
+        }
+        assert!(escaped > 0 && interior > cardioid && cardioid > 0);
+    }
+
+    #[test]
+    fn lanes_match_scalar_f32() {
+        lanes_match_scalar::<f32>();
+    }
+
+    #[test]
+    fn lanes_match_scalar_f64() {
+        lanes_match_scalar::<f64>();
+    }
+
+    #[test]
+    fn mandelbrot_lanes_match_scalar_f32() {
+        mandelbrot_lanes_match_scalar::<f32>();
+    }
+
+    #[test]
+    fn mandelbrot_lanes_match_scalar_f64() {
+        mandelbrot_lanes_match_scalar::<f64>();
+    }
+
+    #[test]
+    fn interior_detection_keeps_estimates_f32() {
+        interior_detection_keeps_estimates::<f32>();
+    }
+
+    #[test]
+    fn interior_detection_keeps_estimates_f64() {
+        interior_detection_keeps_estimates::<f64>();
+    }
+}
@@ -0,0 +1,102 @@
+use std::f64::consts::PI;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use anyhow::{Error, bail};
+
+/// Reconstruction filter weighting the sub-samples of a pixel by their offset from its center.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum Filter {
+    Box,
+    #[default]
+    Tent,
+    /// Mitchell–Netravali cubic with B = C = 1/3.
+    Mitchell,
+    Lanczos,
+    Gaussian,
+}
+
+impl Filter {
+    pub const NAMES: [&str; 5] = ["box", "tent", "mitchell", "lanczos", "gaussian"];
+
+    /// Radius (in pixels) of the support of the filter, unless configured otherwise.
+    pub fn default_radius(&self) -> f64 {
+        match self {
+            Self::Box => 0.5,
+            Self::Tent => 1.0,
+            Self::Mitchell => 2.0,
+            Self::Lanczos => 3.0,
+            Self::Gaussian => 1.5,
+        }
+    }
+
+    /// Weight of a sub-sample at offset `(x, y)` (in pixels) for a filter of the given radius.
+    pub fn weight(&self, x: f64, y: f64, radius: f64) -> f64 {
+        self.kernel(x, radius) * self.kernel(y, radius)
+    }
+
+    fn kernel(&self, x: f64, radius: f64) -> f64 {
+        // Offset relative to the radius, in [0, 1] on the support.
+        let t = x.abs() / radius;
+        if t > 1.0 {
+            return 0.0;
+        }
+
+        match self {
+            Self::Box => 1.0,
+            Self::Tent => 1.0 - t,
+            Self::Mitchell => {
+                // The cubic is defined on [0, 2].
+                let x = 2.0 * t;
+                if x < 1.0 {
+                    (7.0 * x * x * x - 12.0 * x * x + 16.0 / 3.0) / 6.0
+                } else {
+                    (-7.0 / 3.0 * x * x * x + 12.0 * x * x - 20.0 * x + 32.0 / 3.0) / 6.0
+                }
+            }
+            // Lanczos with as many lobes as the radius (in pixels).
+            Self::Lanczos => sinc(x) * sinc(x / radius),
+            // Truncated at 3σ.
+            Self::Gaussian => (-4.5 * t * t).exp(),
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "box" => Ok(Self::Box),
+            "tent" => Ok(Self::Tent),
+            "mitchell" => Ok(Self::Mitchell),
+            "lanczos" => Ok(Self::Lanczos),
+            "gaussian" => Ok(Self::Gaussian),
+            _ => bail!(
+                "Unknown filter {s:?}, expected one of {names}",
+                names = Self::NAMES.join(", ")
+            ),
+        }
+    }
+}
+
+impl Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Box => "box",
+            Self::Tent => "tent",
+            Self::Mitchell => "mitchell",
+            Self::Lanczos => "lanczos",
+            Self::Gaussian => "gaussian",
+        };
+        f.write_str(name)
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt::{Debug, Display};
+use std::ops::{Add, BitAnd, Div, Mul, Sub};
+
+use num_traits::Float;
+use wide::{CmpEq, CmpGt, CmpLt, f32x8, f64x4};
+
+/// Floating point types points are iterated in.
+pub trait Real: Float + Into<f64> + Debug + Display + Default + Send + Sync + 'static {
+    /// As many values as fit into a SIMD register (of AVX).
+    type Lanes: Lanes<Self>;
+
+    /// The value closest to `x`.
+    fn from_f64(x: f64) -> Self;
+}
+
+impl Real for f32 {
+    type Lanes = f32x8;
+
+    fn from_f64(x: f64) -> Self {
+        x as f32
+    }
+}
+
+impl Real for f64 {
+    type Lanes = f64x4;
+
+    fn from_f64(x: f64) -> Self {
+        x
+    }
+}
+
+/// A SIMD vector of [Lanes::LANES] values of type `T`.
+///
+/// Comparisons return masks with all bits of a lane set where they hold.
+pub trait Lanes<T>:
+    Copy
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + BitAnd<Output = Self>
+    + Send
+    + Sync
+{
+    const LANES: usize;
+
+    /// The values of all lanes.
+    type Array: AsRef<[T]> + AsMut<[T]> + Default + Copy;
+
+    fn splat(value: T) -> Self;
+    fn from_array(array: Self::Array) -> Self;
+    fn to_array(self) -> Self::Array;
+
+    fn abs(self) -> Self;
+    fn sqrt(self) -> Self;
+    /// Lane-wise minimum; lanes holding NaN may end up with either value.
+    fn min(self, rhs: Self) -> Self;
+
+    fn cmp_eq(self, rhs: Self) -> Self;
+    fn cmp_gt(self, rhs: Self) -> Self;
+    fn cmp_lt(self, rhs: Self) -> Self;
+
+    /// The lanes of a mask as bits, lane `i` being bit `i`.
+    fn move_mask(self) -> i32;
+
+    /// Lanes of `t` where the mask `self` is set, and of `f` elsewhere.
+    fn blend(self, t: Self, f: Self) -> Self;
+}
+
+macro_rules! impl_lanes {
+    ($lanes:ty, $float:ty, $count:literal) => {
+        impl Lanes<$float> for $lanes {
+            const LANES: usize = $count;
+
+            type Array = [$float; $count];
+
+            fn splat(value: $float) -> Self {
+                <$lanes>::splat(value)
+            }
+
+            fn from_array(array: Self::Array) -> Self {
+                <$lanes>::new(array)
+            }
+
+            fn to_array(self) -> Self::Array {
+                <$lanes>::to_array(self)
+            }
+
+            fn abs(self) -> Self {
+                <$lanes>::abs(self)
+            }
+
+            fn sqrt(self) -> Self {
+                <$lanes>::sqrt(self)
+            }
+
+            fn min(self, rhs: Self) -> Self {
+                <$lanes>::min(self, rhs)
+            }
+
+            fn cmp_eq(self, rhs: Self) -> Self {
+                CmpEq::cmp_eq(self, rhs)
+            }
+
+            fn cmp_gt(self, rhs: Self) -> Self {
+                CmpGt::cmp_gt(self, rhs)
+            }
+
+            fn cmp_lt(self, rhs: Self) -> Self {
+                CmpLt::cmp_lt(self, rhs)
+            }
+
+            fn move_mask(self) -> i32 {
+                <$lanes>::move_mask(self)
+            }
+
+            fn blend(self, t: Self, f: Self) -> Self {
+                <$lanes>::blend(self, t, f)
+            }
+        }
+    };
+}
+
+impl_lanes!(f32x8, f32, 8);
+impl_lanes!(f64x4, f64, 4);
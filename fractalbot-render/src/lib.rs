@@ -0,0 +1,24 @@
+pub mod average;
+pub mod bounding_box;
+mod budget;
+pub mod color;
+pub mod coloring;
+pub mod complex;
+pub mod distance_estimation;
+pub mod filter;
+pub mod float;
+pub mod inverse_iteration;
+pub mod precision;
+pub mod progress;
+pub mod pyramid;
+pub mod relief;
+mod render;
+mod sampling;
+mod subdivision;
+pub mod symmetry;
+pub mod trap;
+
+pub use crate::render::{
+    HEIGHT, MAX_ITER, RenderRequest, RenderedImage, WIDTH, encode_png, render,
+};
+pub use crate::sampling::{MAX_RADIUS, SAMPLES, Sampling};
@@ -0,0 +1,111 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use anyhow::{Error, bail};
+use log::warn;
+
+use crate::{bounding_box::BoundingBox, sampling::Sampling};
+
+/// Number of rounding steps adjacent sub-samples must be apart to be iterated in a precision.
+///
+/// Iteration amplifies rounding errors along with the distance to neighbouring
+/// points, so points need to be resolved much finer than their spacing.
+const RESOLUTION: f64 = 1024.0;
+
+/// Floating point precision points are iterated in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Precision {
+    /// `f32`, iterating twice as many points at once.
+    Single,
+    /// `f64`
+    Double,
+}
+
+impl Precision {
+    pub const NAMES: [&str; 2] = ["single", "double"];
+
+    /// The lowest precision resolving the sub-samples of an image of size
+    /// `width`×`height` spanning `bbx`, unless `sampling` asks for a precision.
+    ///
+    /// Views of whole sets are rendered in single precision at typical sizes,
+    /// while deep zooms and huge images need double precision.
+    pub fn choose(bbx: &BoundingBox, width: u32, height: u32, sampling: &Sampling) -> Self {
+        if let Some(precision) = sampling.precision {
+            return precision;
+        }
+
+        let dim = bbx.abs_dimension();
+        let pixel = (dim.re / f64::from(width)).min(dim.im / f64::from(height));
+        let spacing = pixel * 2.0 * sampling.radius() / f64::from(sampling.grid());
+        let rounding = |epsilon: f64| RESOLUTION * epsilon * bbx.magnitude();
+
+        if spacing > rounding(f32::EPSILON.into()) {
+            return Self::Single;
+        }
+        if spacing <= rounding(f64::EPSILON) {
+            warn!("Pixels are too small to be resolved in double precision, expect artifacts");
+        }
+        Self::Double
+    }
+}
+
+impl FromStr for Precision {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "single" | "f32" => Ok(Self::Single),
+            "double" | "f64" => Ok(Self::Double),
+            _ => bail!(
+                "Unknown precision {s:?}, expected one of {names}",
+                names = Self::NAMES.join(", ")
+            ),
+        }
+    }
+}
+
+impl Display for Precision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Single => "single",
+            Self::Double => "double",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        complex::Complex,
+        render::{HEIGHT, RenderRequest, WIDTH},
+    };
+
+    #[test]
+    fn whole_sets_in_single_precision() {
+        let request = RenderRequest::default();
+        for c in [
+            Complex::new(-1.0, 0.0),
+            Complex::new(-0.123, 0.745),
+            Complex::new(-0.7269, 0.1889),
+        ] {
+            let bbx = request.frame(c);
+            let (width, height) = bbx.fit(WIDTH, HEIGHT);
+            let precision = Precision::choose(&bbx, width, height, &request.sampling);
+            assert_eq!(precision, Precision::Single, "c = {c}");
+        }
+    }
+
+    #[test]
+    fn deep_zooms_in_double_precision() {
+        let request = RenderRequest::default();
+        let bbx = request.frame(Complex::new(-0.123, 0.745));
+        // 100×100 pixels of an image a million pixels wide.
+        let size = 1_000_000;
+        let zoom = bbx.region(300_000..300_100, 600_000..600_100, size, size);
+        let (width, height) = zoom.fit(WIDTH, HEIGHT);
+        let precision = Precision::choose(&zoom, width, height, &request.sampling);
+        assert_eq!(precision, Precision::Double);
+    }
+}
@@ -0,0 +1,152 @@
+use std::fmt::{self, Display};
+use std::io::{IsTerminal, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use log::{Level, debug, info, log_enabled};
+
+/// Time between progress reports in the log.
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Time between redraws of the progress bar.
+const BAR_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Width of the progress bar in characters.
+const BAR_WIDTH: usize = 30;
+
+/// Time spent in each phase of a run, in the order the phases were first entered.
+#[derive(Debug, Clone, Default)]
+pub struct Timings(Vec<(&'static str, Duration)>);
+
+impl Timings {
+    /// Add `duration` to the time spent in `phase`.
+    pub fn add(&mut self, phase: &'static str, duration: Duration) {
+        match self.0.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += duration,
+            None => self.0.push((phase, duration)),
+        }
+    }
+
+    /// Run `f`, adding the time it takes to `phase`.
+    pub fn time<T>(&mut self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        let elapsed = start.elapsed();
+
+        debug!("{phase} took {elapsed:.2?}");
+        self.add(phase, elapsed);
+        result
+    }
+
+    /// Add the times spent in the phases of `other`.
+    pub fn extend(&mut self, other: &Timings) {
+        for &(phase, duration) in &other.0 {
+            self.add(phase, duration);
+        }
+    }
+
+    /// Time spent in `phase`, zero if it was never entered.
+    pub fn get(&self, phase: &str) -> Duration {
+        self.0
+            .iter()
+            .find(|(name, _)| *name == phase)
+            .map_or(Duration::ZERO, |&(_, duration)| duration)
+    }
+
+    pub fn total(&self) -> Duration {
+        self.0.iter().map(|(_, duration)| duration).sum()
+    }
+}
+
+impl Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (phase, duration)) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{phase} {duration:.2?}")?;
+        }
+        write!(f, " (total {:.2?})", self.total())
+    }
+}
+
+/// Tracks how much of a render is done, and reports it with an estimate of the
+/// remaining time: as a progress bar if stderr is a terminal, or as periodic log lines.
+///
+/// Progress can be advanced from many threads at once.
+pub struct Progress {
+    total: u64,
+    done: AtomicU64,
+    start: Instant,
+    last_report: Mutex<Instant>,
+    /// Whether to report progress at all.
+    visible: bool,
+    bar: bool,
+    /// Whether the progress bar has been drawn and needs to be finished.
+    drawn: AtomicBool,
+}
+
+impl Progress {
+    /// Track progress of `total` units of work.
+    pub fn new(total: u64) -> Self {
+        let now = Instant::now();
+        Self {
+            total: total.max(1),
+            done: AtomicU64::new(0),
+            start: now,
+            last_report: Mutex::new(now),
+            visible: true,
+            bar: log_enabled!(Level::Info) && std::io::stderr().is_terminal(),
+            drawn: AtomicBool::new(false),
+        }
+    }
+
+    /// Track progress without reporting it.
+    pub fn hidden() -> Self {
+        Self {
+            visible: false,
+            ..Self::new(0)
+        }
+    }
+
+    /// Record that `amount` more units of work are done, and report progress if it is time to.
+    pub fn advance(&self, amount: u64) {
+        let done = self.done.fetch_add(amount, Ordering::Relaxed) + amount;
+        if !self.visible {
+            return;
+        }
+
+        let interval = if self.bar { BAR_INTERVAL } else { LOG_INTERVAL };
+        // Some other thread is reporting already.
+        let Ok(mut last_report) = self.last_report.try_lock() else {
+            return;
+        };
+        if last_report.elapsed() < interval {
+            return;
+        }
+        *last_report = Instant::now();
+
+        let fraction = (done as f64 / self.total as f64).min(1.0);
+        let eta = self.start.elapsed().mul_f64((1.0 - fraction) / fraction);
+        if self.bar {
+            let filled = (fraction * BAR_WIDTH as f64) as usize;
+            eprint!(
+                "\r[{:<BAR_WIDTH$}] {:>3.0}% (ETA {eta:.0?})  ",
+                "#".repeat(filled),
+                100.0 * fraction
+            );
+            let _ = std::io::stderr().flush();
+            self.drawn.store(true, Ordering::Relaxed);
+        } else {
+            info!("Rendered {:.0}% (ETA {eta:.0?})", 100.0 * fraction);
+        }
+    }
+
+    /// Stop reporting progress.
+    pub fn finish(&self) {
+        if self.drawn.load(Ordering::Relaxed) {
+            eprintln!("\r[{}] 100%{:16}", "#".repeat(BAR_WIDTH), "");
+        }
+    }
+}
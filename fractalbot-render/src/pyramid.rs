@@ -0,0 +1,132 @@
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use log::{debug, info};
+use rand::{Rng, RngExt};
+
+use crate::budget::Output;
+use crate::progress::Timings;
+use crate::render::{RenderRequest, Renderer};
+
+/// Size (in pixels) of the tiles of a Deep Zoom (DZI) pyramid.
+pub const TILE_SIZE: u32 = 256;
+
+/// Number of pixels shared by adjacent tiles, hiding seams when viewers scale tiles.
+const OVERLAP: u32 = 1;
+
+/// One resolution of a tile pyramid.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Level {
+    level: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+impl Level {
+    /// All levels of a pyramid for an image of size `width`×`height`,
+    /// from a single pixel (level 0) up to the full size.
+    pub(crate) fn pyramid(width: u32, height: u32) -> impl Iterator<Item = Self> {
+        let max_level = width.max(height).next_power_of_two().trailing_zeros();
+        (0..=max_level).map(move |level| {
+            let scale = 1 << (max_level - level);
+            Self {
+                level,
+                width: width.div_ceil(scale),
+                height: height.div_ceil(scale),
+            }
+        })
+    }
+
+    /// Pixels covered by tile number `index` along an axis of `len` pixels.
+    fn tile_range(index: u32, len: u32) -> Range<u32> {
+        let start = (index * TILE_SIZE).saturating_sub(OVERLAP);
+        let end = ((index + 1) * TILE_SIZE + OVERLAP).min(len);
+        start..end
+    }
+}
+
+/// Directory containing the tiles described by the descriptor at `path`.
+fn tiles_dir(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}_files"))
+}
+
+impl RenderRequest {
+    /// Render a Deep Zoom tile pyramid of the image, for exploring it in a browser
+    /// (e.g. with OpenSeadragon).
+    ///
+    /// The descriptor is written to `path` (e.g. `fractal.dzi`), and the tiles to
+    /// `fractal_files/<level>/<column>_<row>.png`. Every level is rendered at its
+    /// own resolution rather than downsampled from the full image, so all levels are sharp.
+    ///
+    /// Returns the time spent in each phase.
+    pub fn render_pyramid<R: Rng + ?Sized>(&self, rng: &mut R, path: &Path) -> Result<Timings> {
+        let mut timings = Timings::default();
+        let request = self.plan_for(rng, &mut timings, Output::Pyramid);
+        let c = request.sample_parameter(rng);
+        let palette = rng.sample(request.palette);
+        let bbx = timings.time("bounding", || request.frame(c));
+
+        let (width, height) = bbx.fit(request.width, request.height);
+        if width == 0 || height == 0 {
+            bail!("Cannot render an image of size {width}x{height}");
+        }
+        info!("Rendering tile pyramid for an image of size {width}x{height}");
+
+        let pixels = Level::pyramid(width, height)
+            .map(|level| u64::from(level.width) * u64::from(level.height))
+            .sum();
+        let renderer = Renderer::new(c, palette, request.max_iter, &request.sampling, pixels);
+        let dir = tiles_dir(path);
+        // Kept apart to list the phases in order, although they interleave.
+        let mut saving = Timings::default();
+
+        for Level {
+            level,
+            width,
+            height,
+        } in Level::pyramid(width, height)
+        {
+            let (columns, rows) = (width.div_ceil(TILE_SIZE), height.div_ceil(TILE_SIZE));
+            info!("Rendering level {level} ({width}x{height}, {columns}x{rows} tiles)");
+
+            let level_dir = dir.join(level.to_string());
+            fs::create_dir_all(&level_dir)
+                .with_context(|| format!("Failed to create {}", level_dir.display()))?;
+
+            for row in 0..rows {
+                for column in 0..columns {
+                    let tile = renderer.render_region(
+                        &bbx,
+                        width,
+                        height,
+                        Level::tile_range(column, width),
+                        Level::tile_range(row, height),
+                    );
+
+                    let tile_path = level_dir.join(format!("{column}_{row}.png"));
+                    saving
+                        .time("saving", || tile.save(&tile_path))
+                        .with_context(|| format!("Failed to save tile {}", tile_path.display()))?;
+                    debug!("Saved tile {}", tile_path.display());
+                }
+            }
+        }
+        timings.extend(&renderer.finish());
+        timings.extend(&saving);
+
+        // Write the descriptor last, so that it only exists for complete pyramids.
+        let descriptor = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" \
+             Format=\"png\" Overlap=\"{OVERLAP}\" TileSize=\"{TILE_SIZE}\">\n\
+             \x20   <Size Width=\"{width}\" Height=\"{height}\"/>\n\
+             </Image>\n"
+        );
+        fs::write(path, descriptor)
+            .with_context(|| format!("Failed to write descriptor {}", path.display()))?;
+        Ok(timings)
+    }
+}
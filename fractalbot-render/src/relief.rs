@@ -0,0 +1,74 @@
+use crate::{
+    color::{Srgb, linear_to_srgb, srgb_to_linear},
+    distance_estimation::Orbit,
+};
+
+/// Elevation of the light above the plane of the image, in degrees.
+const ELEVATION: f64 = 45.0;
+
+/// Height of the relief: the larger, the flatter it looks.
+const HEIGHT: f64 = 1.5;
+
+/// Brightness (in linear light) of parts of the relief facing away from the light.
+const AMBIENT: f32 = 0.3;
+
+/// Strength of highlights.
+const SPECULAR: f32 = 0.3;
+
+/// Blinn-Phong exponent: the larger, the smaller the highlights.
+const SHININESS: i32 = 20;
+
+/// Lights the exterior of a set as if it were a height field rising towards the set,
+/// giving the embossed look of many fractal renders.
+///
+/// Level sets of the potential (and of the distance estimate, to first order) are the
+/// contours of the height field. Its slope at a point faces `z_n / z_n'`, where `z_n'`
+/// is the derivative of the last iterate (see [Orbit::direction]). Colors are shaded by
+/// Lambert's law, with Blinn-Phong highlights for a viewer looking straight down.
+#[derive(Debug, Copy, Clone)]
+pub struct Relief {
+    /// Unit vector towards the light, in the complex plane and out of it.
+    light: [f64; 3],
+    /// Unit vector halfway between the light and the viewer.
+    halfway: [f64; 3],
+}
+
+impl Relief {
+    /// Relief lit from `angle` degrees, counterclockwise from the right edge of the image.
+    pub fn new(angle: f64) -> Self {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let (up, across) = ELEVATION.to_radians().sin_cos();
+        // Imaginary parts grow downwards in images.
+        let light = [across * cos, -across * sin, up];
+        let halfway = normalize([light[0], light[1], light[2] + 1.0]);
+        Self { light, halfway }
+    }
+
+    /// Shade `color` of a point outside the set by the slope of the relief there.
+    pub fn shade(&self, orbit: &Orbit, color: Srgb) -> Srgb {
+        let slope = orbit.z * orbit.direction.conj();
+        let slope = slope / slope.norm();
+        if !slope.re.is_finite() || !slope.im.is_finite() {
+            // The derivative vanished, e.g. at the critical point.
+            return color;
+        }
+
+        let normal = normalize([slope.re, slope.im, HEIGHT]);
+        let diffuse = dot(normal, self.light).max(0.0) as f32;
+        let specular = dot(normal, self.halfway).max(0.0).powi(SHININESS) as f32;
+
+        let brightness = AMBIENT + (1.0 - AMBIENT) * diffuse;
+        color.map(|channel| {
+            linear_to_srgb((brightness * srgb_to_linear(channel) + SPECULAR * specular).min(1.0))
+        })
+    }
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(v: [f64; 3]) -> [f64; 3] {
+    let norm = dot(v, v).sqrt();
+    v.map(|x| x / norm)
+}
@@ -0,0 +1,676 @@
+use std::io::{Cursor, Write};
+use std::ops::Range;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use image::{ImageFormat, Luma, Rgb, RgbImage, imageops};
+use log::{debug, info};
+use rand::{Rng, RngExt};
+use rayon::prelude::{IndexedParallelIterator, ParallelIterator};
+
+use crate::{
+    bounding_box::BoundingBox,
+    color::{Palette, PaletteChoice, Srgb},
+    coloring::{Coloring, Colorizer, DistanceColorizer, Interior, InteriorColorizer},
+    complex::Complex,
+    distance_estimation::{
+        ConnectedBoundary, DistanceEstimation, MandelbrotBoundary, MandelbrotEstimation, Orbit,
+    },
+    float::Real,
+    inverse_iteration::InverseIteration,
+    precision::Precision,
+    progress::{Progress, Timings},
+    relief::Relief,
+    sampling::{AdaptiveSampler, Colors, Distances, Sampling},
+    subdivision,
+    symmetry::GridSymmetry,
+    trap::TrapChoice,
+};
+
+pub const WIDTH: u32 = 1280;
+pub const HEIGHT: u32 = 1280;
+pub const ITER: usize = 10_000;
+pub const MAX_ITER: usize = 4096;
+
+/// Corners of the bounding box of the Mandelbrot set.
+const MANDELBROT: [Complex; 2] = [Complex::new(-2.0, -1.135), Complex::new(0.471, 1.135)];
+
+/// What to render: which Julia set, how to frame it and how to color it.
+///
+/// Choices left open (the parameter and the palette) are sampled randomly
+/// by [RenderRequest::render].
+#[derive(Debug, Clone)]
+pub struct RenderRequest {
+    /// Parameter `c` of `f(z) = z² + c`, sampled near the boundary of the Mandelbrot set if not given.
+    pub parameter: Option<Complex>,
+    /// If given, only sample parameters whose Julia set is (dis)connected.
+    pub connected: Option<bool>,
+    pub palette: PaletteChoice,
+    /// Maximum width of the image; the image is fit to the aspect ratio of the set.
+    pub width: u32,
+    /// Maximum height of the image; the image is fit to the aspect ratio of the set.
+    pub height: u32,
+    /// Size of the view relative to the bounding box of the set.
+    pub margin: f64,
+    pub max_iter: usize,
+    pub sampling: Sampling,
+    /// Wall-clock time rendering may take; quality is reduced to fit (see [RenderRequest::plan]).
+    pub budget: Option<Duration>,
+    /// Trap to place for [Coloring::Trap], fixed as [Sampling::trap] by [RenderRequest::plan].
+    pub trap: TrapChoice,
+    /// Frequency of [Coloring::Stripes], random if not given;
+    /// fixed as [Sampling::stripes] by [RenderRequest::plan].
+    pub stripes: Option<u32>,
+    /// Weight of averages for [Coloring::Stripes] and [Coloring::Curvature], random
+    /// if not given; fixed as [Sampling::mix] by [RenderRequest::plan].
+    pub mix: Option<f64>,
+    /// Angle of the light for [Sampling::relief], random if not given;
+    /// fixed as [Sampling::light] by [RenderRequest::plan].
+    pub light: Option<f64>,
+}
+
+impl Default for RenderRequest {
+    fn default() -> Self {
+        Self {
+            parameter: None,
+            connected: None,
+            palette: PaletteChoice::default(),
+            width: WIDTH,
+            height: HEIGHT,
+            margin: 1.20,
+            max_iter: MAX_ITER,
+            sampling: Sampling::default(),
+            budget: None,
+            trap: TrapChoice::default(),
+            stripes: None,
+            mix: None,
+            light: None,
+        }
+    }
+}
+
+/// A rendered Julia set, together with the choices made to render it.
+pub struct RenderedImage {
+    pub c: Complex,
+    pub palette: Palette,
+    /// Region of the complex plane shown in the image.
+    pub bbx: BoundingBox,
+    pub image: RgbImage,
+    /// Time spent in each phase of rendering.
+    pub timings: Timings,
+}
+
+impl RenderRequest {
+    /// The requested parameter `c`, or a random one.
+    pub fn sample_parameter<R: Rng + ?Sized>(&self, rng: &mut R) -> Complex {
+        let c = self.parameter.unwrap_or_else(|| match self.connected {
+            Some(connected) => rng.sample(ConnectedBoundary {
+                max_iter: self.max_iter,
+                connected,
+            }),
+            None => rng.sample(MandelbrotBoundary {
+                max_iter: self.max_iter,
+            }),
+        });
+
+        info!("Julia parameter: c = {c}");
+        c
+    }
+
+    /// Approximate the bounding box of the Julia set via inverse iteration, and add a margin.
+    ///
+    /// For [Sampling::mandelbrot], frame the Mandelbrot set instead, ignoring `c`.
+    pub fn frame(&self, c: Complex) -> BoundingBox {
+        if self.sampling.mandelbrot {
+            let [min, max] = MANDELBROT;
+            let center = (min + max) * 0.5;
+            return [min, max]
+                .map(|corner| center + (corner - center) * self.margin)
+                .into_iter()
+                .collect();
+        }
+
+        let julia: InverseIteration = InverseIteration::new(c);
+
+        let mut bbx: BoundingBox = julia // Julia::new(Complex::new(-0.12, 0.74))
+            .into_iter()
+            .take(ITER)
+            .collect();
+        bbx.scale(self.margin);
+
+        info!(
+            "Bounding box has aspect ratio of {ratio:.2}:1",
+            ratio = bbx.aspect_ratio()
+        );
+        bbx
+    }
+
+    /// Render the image, failing if it would be empty.
+    pub fn render<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<RenderedImage> {
+        let mut timings = Timings::default();
+        let mut rendered = self.plan(rng, &mut timings).render_planned(rng)?;
+        timings.extend(&rendered.timings);
+        rendered.timings = timings;
+        Ok(rendered)
+    }
+
+    /// Like [RenderRequest::render], for a request returned by [RenderRequest::plan],
+    /// which is not planned again.
+    pub fn render_planned<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<RenderedImage> {
+        let mut timings = Timings::default();
+        let c = self.sample_parameter(rng);
+        let palette = rng.sample(self.palette);
+        let bbx = timings.time("bounding", || self.frame(c));
+
+        let (width, height) = bbx.fit(self.width, self.height);
+        if width == 0 || height == 0 {
+            bail!("Cannot render an image of size {width}x{height}");
+        }
+        let renderer = Renderer::new(
+            c,
+            palette,
+            self.max_iter,
+            &self.sampling,
+            u64::from(width) * u64::from(height),
+        );
+        let (image, _) = renderer.render_view(&bbx, width, height);
+        timings.extend(&renderer.finish());
+
+        Ok(RenderedImage {
+            c,
+            palette,
+            bbx,
+            image,
+            timings,
+        })
+    }
+}
+
+/// Render the Julia set of `f(z) = z² + c` in the region `bbx` of the complex plane,
+/// colored using `palette`.
+///
+/// Every pixel is sampled once, and pixels near the boundary of the set or at
+/// sharp edges are refined with jittered sub-samples, as configured by `sampling`.
+///
+/// Where the region overlaps its mirror image under z → -z, only half of the
+/// pixels are rendered, and the region is shifted by a fraction of a pixel to make
+/// mirrored pixels line up.
+pub fn render(
+    c: Complex,
+    palette: Palette,
+    bbx: &BoundingBox,
+    width: u32,
+    height: u32,
+    max_iter: usize,
+    sampling: &Sampling,
+) -> RgbImage {
+    let pixels = u64::from(width) * u64::from(height);
+    let renderer = Renderer::new(c, palette, max_iter, sampling, pixels);
+    let (image, _) = renderer.render_view(bbx, width, height);
+    renderer.finish();
+    image
+}
+
+/// Number of pixels rendered at once by [RenderRequest::render_png].
+const BAND_PIXELS: u32 = 4 << 20;
+
+impl RenderRequest {
+    /// Like [RenderRequest::render], but render the image in bands of rows and
+    /// encode each band to `writer` as soon as it is done.
+    ///
+    /// Memory use is bounded by the size of a band, no matter how large the image is.
+    /// Bands are rendered without exploiting the symmetry of the set.
+    ///
+    /// Returns the time spent in each phase.
+    pub fn render_png<R, W>(&self, rng: &mut R, writer: W) -> Result<Timings>
+    where
+        R: Rng + ?Sized,
+        W: Write,
+    {
+        let mut timings = Timings::default();
+        let request = self.plan(rng, &mut timings);
+        let c = request.sample_parameter(rng);
+        let palette = rng.sample(request.palette);
+        let bbx = timings.time("bounding", || request.frame(c));
+
+        let (width, height) = bbx.fit(request.width, request.height);
+        if width == 0 || height == 0 {
+            bail!("Cannot render an image of size {width}x{height}");
+        }
+        info!("Streaming image of size {width}x{height}");
+
+        let pixels = u64::from(width) * u64::from(height);
+        let renderer = Renderer::new(c, palette, request.max_iter, &request.sampling, pixels);
+
+        let mut encoder = png::Encoder::new(writer, width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder
+            .write_header()
+            .context("Failed to start encoding image")?;
+        let mut stream = writer
+            .stream_writer()
+            .context("Failed to start encoding image")?;
+
+        // Kept apart to list the phases in order, although they interleave.
+        let mut encoding = Timings::default();
+        let band_rows = (BAND_PIXELS / width).max(1);
+        for y in (0..height).step_by(band_rows as usize) {
+            let rows = y..(y + band_rows).min(height);
+            let band = renderer.render_region(&bbx, width, height, 0..width, rows.clone());
+
+            encoding
+                .time("encoding", || stream.write_all(band.as_raw()))
+                .context("Failed to write image")?;
+
+            debug!("Wrote rows {}..{}", rows.start, rows.end);
+        }
+        encoding
+            .time("encoding", || stream.finish())
+            .context("Failed to finish image")?;
+        encoding
+            .time("encoding", || writer.finish())
+            .context("Failed to finish image")?;
+
+        timings.extend(&renderer.finish());
+        timings.extend(&encoding);
+        Ok(timings)
+    }
+}
+
+/// Everything needed to render parts of a Julia set (or the Mandelbrot set), at any resolution.
+pub(crate) struct Renderer {
+    /// Distance estimation for each precision, see [Precision::choose].
+    single: DistanceEstimation<f32>,
+    double: DistanceEstimation<f64>,
+    /// Distance estimation of the Mandelbrot set for each precision, if it is rendered
+    /// instead of the Julia set.
+    mandelbrot: Option<(MandelbrotEstimation<f32>, MandelbrotEstimation<f64>)>,
+    palette: Palette,
+    colorizer: Box<dyn Colorizer>,
+    interior: Option<InteriorColorizer>,
+    relief: Option<Relief>,
+    sampling: Sampling,
+    progress: Progress,
+    /// Time spent rendering and refining, summed over all parts.
+    timings: Mutex<Timings>,
+}
+
+impl Renderer {
+    /// A renderer for parts of images totalling `pixels` pixels.
+    pub(crate) fn new(
+        c: Complex,
+        palette: Palette,
+        max_iter: usize,
+        sampling: &Sampling,
+        pixels: u64,
+    ) -> Self {
+        // Every pixel is visited twice: once to sample it, once to refine it.
+        let renderer =
+            Self::with_progress(c, palette, max_iter, sampling, Progress::new(2 * pixels));
+
+        if sampling.mandelbrot {
+            info!("Rendering the Mandelbrot set");
+        } else if renderer.double.is_connected() {
+            info!("Julia set is connected");
+        } else {
+            info!("Julia set is disconnected");
+        }
+
+        info!("Coloring by {}", sampling.coloring);
+        if sampling.coloring == Coloring::Trap {
+            info!("Trapping orbits in a {}", sampling.trap);
+        }
+        if let Some(average) = sampling.coloring.average(sampling) {
+            info!("Averaging {average}, mixed by {:.2}", sampling.mix);
+        }
+        if sampling.relief {
+            info!("Lighting relief from {:.0}°", sampling.light);
+        }
+        if let Some(interior) = sampling.interior {
+            info!("Coloring the interior by {interior}");
+        }
+        debug!("Palette: {:.2?}", palette);
+        debug!("Color for d=0.0: {:?}", palette.pick(0.0));
+
+        info!(
+            "Refining with {} samples per pixel and a {} filter of radius {}",
+            sampling.grid().pow(2),
+            sampling.filter,
+            sampling.radius(),
+        );
+
+        renderer
+    }
+
+    /// Like [Renderer::new], but without logging or reporting progress,
+    /// for renders that are not the point of the run (e.g. previews).
+    pub(crate) fn quiet(
+        c: Complex,
+        palette: Palette,
+        max_iter: usize,
+        sampling: &Sampling,
+    ) -> Self {
+        Self::with_progress(c, palette, max_iter, sampling, Progress::hidden())
+    }
+
+    fn with_progress(
+        c: Complex,
+        palette: Palette,
+        max_iter: usize,
+        sampling: &Sampling,
+        progress: Progress,
+    ) -> Self {
+        let mut double = DistanceEstimation::new(c, max_iter);
+        if sampling.coloring == Coloring::Trap {
+            double = double.with_trap(sampling.trap);
+        }
+        if let Some(average) = sampling.coloring.average(sampling) {
+            double = double.with_average(average);
+        }
+        if sampling.relief {
+            double = double.with_direction();
+        }
+        if sampling.interior == Some(Interior::Distance) {
+            double = double.with_entry();
+        }
+        let single = double.cast();
+
+        let mandelbrot = sampling.mandelbrot.then(|| {
+            let mut double = MandelbrotEstimation::new(max_iter);
+            if sampling.coloring == Coloring::Trap {
+                double = double.with_trap(sampling.trap);
+            }
+            if let Some(average) = sampling.coloring.average(sampling) {
+                double = double.with_average(average);
+            }
+            if sampling.relief {
+                double = double.with_direction();
+            }
+            (double.cast(), double)
+        });
+
+        // The Mandelbrot set is connected, but has no attracting cycle to color its interior by.
+        let connected = sampling.mandelbrot || double.is_connected();
+        let colorizer = sampling.coloring.colorizer(connected, sampling);
+        let interior = sampling.interior.map(|interior| InteriorColorizer {
+            interior,
+            cycle: (!sampling.mandelbrot)
+                .then(|| double.attracting_cycle().cloned())
+                .flatten(),
+            distance: DistanceColorizer::new(true),
+        });
+
+        Self {
+            single,
+            double,
+            mandelbrot,
+            palette,
+            colorizer,
+            interior,
+            relief: sampling.relief.then(|| Relief::new(sampling.light)),
+            sampling: *sampling,
+            progress,
+            timings: Mutex::default(),
+        }
+    }
+
+    /// Stop reporting progress, and return the time spent rendering.
+    pub(crate) fn finish(self) -> Timings {
+        self.progress.finish();
+
+        let timings = self.timings.into_inner().unwrap();
+        debug!("Render timings: {timings}");
+        timings
+    }
+
+    /// Iterate `points` in precision `T`, for the set being rendered.
+    fn orbits<T: Iterate>(&self, points: &[Complex<T>], orbits: &mut [Orbit]) {
+        match T::mandelbrot(self) {
+            Some(mandelbrot) => mandelbrot.orbits(points, orbits),
+            None => T::julia(self).orbits(points, orbits),
+        }
+    }
+
+    /// Colors of the given points, which are iterated in batches.
+    fn colors<T: Iterate>(&self, points: &[Complex<T>]) -> Vec<Srgb> {
+        let mut orbits = vec![Orbit::default(); points.len()];
+        self.orbits(points, &mut orbits);
+        orbits.iter().map(|orbit| self.color(orbit)).collect()
+    }
+
+    fn add_time(&self, phase: &'static str, duration: Duration) {
+        self.timings.lock().unwrap().add(phase, duration);
+    }
+
+    /// Render an image of size `width`×`height` spanning `bbx`.
+    ///
+    /// Where `bbx` overlaps its mirror image under z → -z, only half of the
+    /// pixels are rendered, and the region is shifted by a fraction of a pixel to make
+    /// mirrored pixels line up. Not so with a [Relief], which is lit from one side,
+    /// nor for the Mandelbrot set, which is only symmetric under z → z̄.
+    ///
+    /// Returns the image and the number of refined pixels, not counting mirror images.
+    pub(crate) fn render_view(
+        &self,
+        bbx: &BoundingBox,
+        width: u32,
+        height: u32,
+    ) -> (RgbImage, usize) {
+        let mut bbx = bbx.clone();
+        let symmetry = if self.relief.is_some() || self.mandelbrot.is_some() {
+            GridSymmetry::none(width, height)
+        } else {
+            bbx.snap_symmetric(width, height)
+        };
+
+        let sampler = AdaptiveSampler::new(&self.sampling, &bbx, width, height);
+        let precision = Precision::choose(&bbx, width, height, &self.sampling);
+        info!("Iterating in {precision} precision");
+
+        let (image, refined) = match precision {
+            Precision::Single => {
+                let bbx = bbx.cast::<f32>();
+                self.render_grid(&sampler, &bbx, width, height, (0, 0), symmetry)
+            }
+            Precision::Double => self.render_grid(&sampler, &bbx, width, height, (0, 0), symmetry),
+        };
+
+        debug!("Refined {refined} pixels, not counting mirror images");
+        (image, refined)
+    }
+
+    fn color(&self, orbit: &Orbit) -> Srgb {
+        if orbit.distance <= 0.0 {
+            return match &self.interior {
+                Some(interior) => interior.color(orbit, &self.palette),
+                None => [0.0; 3],
+            };
+        }
+        let color = self.colorizer.color(orbit, &self.palette);
+        match &self.relief {
+            Some(relief) => relief.shade(orbit, color),
+            None => color,
+        }
+    }
+
+    /// Render the pixels `columns`×`rows` of an image of size `width`×`height` spanning `bbx`.
+    pub(crate) fn render_region(
+        &self,
+        bbx: &BoundingBox,
+        width: u32,
+        height: u32,
+        columns: Range<u32>,
+        rows: Range<u32>,
+    ) -> RgbImage {
+        // Render extra pixels on each side, to compare and filter pixels with their neighbours.
+        let sampler = AdaptiveSampler::new(&self.sampling, bbx, width, height);
+        let reach = sampler.reach();
+        let (x_min, x_max) = (
+            columns.start.saturating_sub(reach),
+            (columns.end + reach).min(width),
+        );
+        let (y_min, y_max) = (
+            rows.start.saturating_sub(reach),
+            (rows.end + reach).min(height),
+        );
+        let (region_width, region_height) = (x_max - x_min, y_max - y_min);
+
+        let region = bbx.region(x_min..x_max, y_min..y_max, width, height);
+        let origin = (x_min, y_min);
+        let symmetry = GridSymmetry::none(region_width, region_height);
+        let (image, _) = match Precision::choose(bbx, width, height, &self.sampling) {
+            Precision::Single => {
+                let region = region.cast::<f32>();
+                self.render_grid(
+                    &sampler,
+                    &region,
+                    region_width,
+                    region_height,
+                    origin,
+                    symmetry,
+                )
+            }
+            Precision::Double => self.render_grid(
+                &sampler,
+                &region,
+                region_width,
+                region_height,
+                origin,
+                symmetry,
+            ),
+        };
+
+        imageops::crop_imm(
+            &image,
+            columns.start - x_min,
+            rows.start - y_min,
+            columns.len() as u32,
+            rows.len() as u32,
+        )
+        .to_image()
+    }
+
+    /// Render an image of size `width`×`height` spanning `bbx`, whose top left
+    /// pixel is pixel `origin` of the whole image.
+    ///
+    /// Points are iterated in precision `T`.
+    ///
+    /// Returns the image and the number of refined pixels.
+    fn render_grid<T: Iterate>(
+        &self,
+        sampler: &AdaptiveSampler,
+        bbx: &BoundingBox<T>,
+        width: u32,
+        height: u32,
+        origin: (u32, u32),
+        symmetry: GridSymmetry,
+    ) -> (RgbImage, usize) {
+        let color = |orbit: &Orbit| self.color(orbit);
+        let (x_origin, y_origin) = origin;
+        let julia = T::julia(self);
+
+        let start = Instant::now();
+        let mut distances = Distances::new(width, height);
+        let mut colors = Colors::new(width, height);
+        // Subdivision fills the interior with orbits that tell nothing about it.
+        if self.sampling.subdivide
+            && self.sampling.interior.is_none()
+            && self.mandelbrot.is_none()
+            && julia.is_connected()
+        {
+            subdivision::sample(
+                julia,
+                bbx,
+                symmetry,
+                &self.progress,
+                &mut distances,
+                &mut colors,
+                color,
+            );
+        } else {
+            let tiles = bbx
+                .par_tiles(&mut distances)
+                .zip(bbx.par_tiles(&mut colors));
+            tiles.for_each(|(mut tile, mut color_tile)| {
+                let (pixels, points): (Vec<_>, Vec<_>) = tile
+                    .points()
+                    .zip(color_tile.points())
+                    .filter(|&((x, y, _, _), _)| symmetry.is_canonical(x, y))
+                    .map(|((_, _, distance, point), (_, _, color, _))| ((distance, color), point))
+                    .unzip();
+
+                let mut orbits = vec![Orbit::default(); points.len()];
+                self.orbits(&points, &mut orbits);
+                for ((distance, pixel), orbit) in pixels.into_iter().zip(orbits) {
+                    *distance = Luma([orbit.distance]);
+                    *pixel = Rgb(color(&orbit));
+                }
+                self.progress.advance(tile.pixels() as u64);
+            });
+        }
+        symmetry.mirror_image(&mut distances);
+        symmetry.mirror_image(&mut colors);
+        self.add_time("rendering", start.elapsed());
+
+        let start = Instant::now();
+        let refined = AtomicUsize::new(0);
+
+        let mut imgbuf = RgbImage::new(width, height);
+        bbx.par_tiles(&mut imgbuf).for_each(|mut tile| {
+            tile.points()
+                .filter(|&(x, y, _, _)| symmetry.is_canonical(x, y))
+                .for_each(|(x, y, pixel, point)| {
+                    let (x_image, y_image) = (x + x_origin, y + y_origin);
+                    let color = if sampler.refine(&distances, &colors, x, y) {
+                        refined.fetch_add(1, Ordering::Relaxed);
+                        sampler.reconstruct(x_image, y_image, point, |points| self.colors(points))
+                    } else {
+                        sampler.filter(&colors, x, y)
+                    };
+                    *pixel = sampler.quantize(x_image, y_image, color);
+                });
+            self.progress.advance(tile.pixels() as u64);
+        });
+        symmetry.mirror_image(&mut imgbuf);
+        self.add_time("refinement", start.elapsed());
+
+        (imgbuf, refined.into_inner())
+    }
+}
+
+/// Precisions a [Renderer] iterates points in.
+trait Iterate: Real {
+    fn julia(renderer: &Renderer) -> &DistanceEstimation<Self>;
+    fn mandelbrot(renderer: &Renderer) -> Option<&MandelbrotEstimation<Self>>;
+}
+
+impl Iterate for f32 {
+    fn julia(renderer: &Renderer) -> &DistanceEstimation<Self> {
+        &renderer.single
+    }
+
+    fn mandelbrot(renderer: &Renderer) -> Option<&MandelbrotEstimation<Self>> {
+        renderer.mandelbrot.as_ref().map(|(single, _)| single)
+    }
+}
+
+impl Iterate for f64 {
+    fn julia(renderer: &Renderer) -> &DistanceEstimation<Self> {
+        &renderer.double
+    }
+
+    fn mandelbrot(renderer: &Renderer) -> Option<&MandelbrotEstimation<Self>> {
+        renderer.mandelbrot.as_ref().map(|(_, double)| double)
+    }
+}
+
+pub fn encode_png(imgbuf: &RgbImage) -> Result<Vec<u8>> {
+    let mut encode_buffer = Cursor::new(Vec::new());
+    imgbuf
+        .write_to(&mut encode_buffer, ImageFormat::Png)
+        .context("Failed to encode image")?;
+    Ok(encode_buffer.into_inner())
+}
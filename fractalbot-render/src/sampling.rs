@@ -0,0 +1,309 @@
+use image::{ImageBuffer, Luma, Rgb};
+
+use crate::{
+    bounding_box::BoundingBox,
+    color::{Srgb, linear_to_srgb, srgb_to_linear},
+    coloring::{Coloring, Interior},
+    complex::Complex,
+    filter::Filter,
+    float::Real,
+    precision::Precision,
+    trap::Trap,
+};
+
+/// Default number of samples per pixel where the image is refined.
+pub const SAMPLES: u32 = 16;
+
+/// Largest radius of the filter (in pixels). The filter of every pixel looks at all
+/// pixels within the radius, so wider filters get slow, and blur the image anyway.
+pub const MAX_RADIUS: f64 = 4.0;
+
+/// Pixels closer to the set than this (in pixels) are refined.
+const BOUNDARY_PIXELS: f64 = 1.0;
+
+/// Pixels differing from a neighbour by more than this in any (sRGB-encoded) channel are refined.
+const CONTRAST: f32 = 16.0 / 255.0;
+
+/// Distance estimates of one sample per pixel, taken at the pixel centers.
+pub type Distances = ImageBuffer<Luma<f64>, Vec<f64>>;
+
+/// Colors of the same samples as [Distances], before quantizing.
+pub type Colors = ImageBuffer<Rgb<f32>, Vec<f32>>;
+
+/// How to sample pixels and turn the samples into colors.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sampling {
+    /// Maximum number of samples per pixel, spent near the boundary of the set,
+    /// all over the support of the filter.
+    pub samples: u32,
+    /// Filter weighting the samples of a pixel.
+    pub filter: Filter,
+    /// Radius of the filter in pixels, [Filter::default_radius] if not given,
+    /// and at most [MAX_RADIUS].
+    pub radius: Option<f64>,
+    /// Whether to dither when quantizing colors, to avoid banding in smooth gradients.
+    pub dither: bool,
+    /// Precision to iterate points in, [chosen](Precision::choose) per image if not given.
+    pub precision: Option<Precision>,
+    /// Whether to skip the insides of interior regions of connected sets
+    /// by subdividing the image (Mariani–Silver), instead of estimating every pixel.
+    pub subdivide: bool,
+    /// What picks the colors of samples from the palette.
+    pub coloring: Coloring,
+    /// Weight of the iteration count against the distance for [Coloring::Blend], in [0, 1].
+    pub blend: f64,
+    /// Trap orbits are measured against for [Coloring::Trap].
+    pub trap: Trap,
+    /// Whether to shade [Coloring::Trap] by the distance to the set.
+    pub shade: bool,
+    /// Frequency of the stripes of [Coloring::Stripes].
+    pub stripes: u32,
+    /// Weight of the average against the distance for [Coloring::Stripes]
+    /// and [Coloring::Curvature], in [0, 1].
+    pub mix: f64,
+    /// Whether to darken every other equipotential band for [Coloring::Binary]
+    /// and [Coloring::FieldLines].
+    pub bands: bool,
+    /// Whether to shade colors by a [Relief](crate::relief::Relief),
+    /// which breaks the symmetry of the set.
+    pub relief: bool,
+    /// Angle the relief is lit from, in degrees (see [Relief::new](crate::relief::Relief::new)).
+    pub light: f64,
+    /// How to color the interior of connected sets, black if not given.
+    pub interior: Option<Interior>,
+    /// Whether to render the Mandelbrot set (iterating from 0, with each point as the
+    /// parameter) instead of the Julia set of the parameter.
+    pub mandelbrot: bool,
+}
+
+impl Sampling {
+    /// Number of sub-samples along each axis of the support of the filter of a refined
+    /// pixel, which spans `2 · radius` pixels; the budget is rounded down to a square number.
+    pub(crate) fn grid(&self) -> u32 {
+        self.samples.isqrt().max(1)
+    }
+
+    /// Radius of the filter in pixels.
+    pub(crate) fn radius(&self) -> f64 {
+        self.radius
+            .unwrap_or_else(|| self.filter.default_radius())
+            .min(MAX_RADIUS)
+    }
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Self {
+            samples: SAMPLES,
+            filter: Filter::default(),
+            radius: None,
+            dither: false,
+            precision: None,
+            subdivide: false,
+            coloring: Coloring::default(),
+            blend: 0.5,
+            trap: Trap::default(),
+            shade: false,
+            stripes: 5,
+            mix: 0.5,
+            bands: false,
+            relief: false,
+            light: 135.0,
+            interior: None,
+            mandelbrot: false,
+        }
+    }
+}
+
+/// Decides which pixels to supersample, and where to place their sub-samples.
+///
+/// Pixels are sampled once first. Only pixels close to the boundary of the set,
+/// or differing a lot from their neighbours, are then refined with a `grid`×`grid`
+/// grid of sub-samples covering the support of the filter, each jittered within its
+/// cell. Sub-samples are weighted and summed in linear light.
+///
+/// The other pixels are filtered just the same, from the samples at the centers of
+/// the pixels around them, so that they are as sharp as refined pixels.
+pub struct AdaptiveSampler {
+    /// Number of sub-samples along each axis of the support of the filter.
+    grid: u32,
+    filter: Filter,
+    radius: f64,
+    /// Offsets of the pixel centers within the support of the filter,
+    /// and their (nonzero) weights.
+    taps: Vec<(i32, i32, f32)>,
+    dither: bool,
+    /// Size of a pixel in the complex plane.
+    pixel: Complex,
+}
+
+impl AdaptiveSampler {
+    /// A sampler for an image of size `width`×`height` spanning `bbx`.
+    pub fn new(sampling: &Sampling, bbx: &BoundingBox, width: u32, height: u32) -> Self {
+        let dim = bbx.abs_dimension();
+        let radius = sampling.radius();
+        let reach = radius.floor() as i32;
+        let taps = (-reach..=reach)
+            .flat_map(|dy| (-reach..=reach).map(move |dx| (dx, dy)))
+            .map(|(dx, dy)| {
+                let weight = sampling.filter.weight(f64::from(dx), f64::from(dy), radius);
+                (dx, dy, weight as f32)
+            })
+            .filter(|&(_, _, weight)| weight != 0.0)
+            .collect();
+        Self {
+            grid: sampling.grid(),
+            filter: sampling.filter,
+            radius,
+            taps,
+            dither: sampling.dither,
+            pixel: Complex::new(dim.re / f64::from(width), dim.im / f64::from(height)),
+        }
+    }
+
+    /// Number of pixels around a pixel that [AdaptiveSampler::refine] and
+    /// [AdaptiveSampler::filter] look at, on each side.
+    pub fn reach(&self) -> u32 {
+        self.taps
+            .iter()
+            .map(|&(dx, dy, _)| dx.unsigned_abs().max(dy.unsigned_abs()))
+            .max()
+            .unwrap_or(0)
+            .max(1)
+    }
+
+    /// Whether pixel `(x, y)` should be refined, given the distances sampled at
+    /// all pixel centers and their colors.
+    pub fn refine(&self, distances: &Distances, colors: &Colors, x: u32, y: u32) -> bool {
+        if self.grid == 1 {
+            return false;
+        }
+
+        let d = distances.get_pixel(x, y)[0];
+        if 0.0 < d && d < BOUNDARY_PIXELS * self.pixel.re.max(self.pixel.im) {
+            return true;
+        }
+
+        let (width, height) = distances.dimensions();
+        let neighbours = [
+            x.checked_sub(1).map(|x| (x, y)),
+            y.checked_sub(1).map(|y| (x, y)),
+            Some((x + 1, y)).filter(|&(x, _)| x < width),
+            Some((x, y + 1)).filter(|&(_, y)| y < height),
+        ];
+
+        let center = colors.get_pixel(x, y).0;
+        neighbours.into_iter().flatten().any(|(nx, ny)| {
+            let neighbour = colors.get_pixel(nx, ny).0;
+            center
+                .iter()
+                .zip(neighbour)
+                .any(|(&a, b)| (a - b).abs() > CONTRAST)
+        })
+    }
+
+    /// Filter the colors sampled at the centers of pixel `(x, y)` and the pixels around
+    /// it, for a pixel that is not refined.
+    ///
+    /// Pixels outside the image are left out. Filters vanishing at all other pixel
+    /// centers (like the default tent, box and Lanczos filters) return the color of
+    /// the pixel itself.
+    pub fn filter(&self, colors: &Colors, x: u32, y: u32) -> Srgb {
+        if let [(0, 0, _)] = self.taps[..] {
+            return colors.get_pixel(x, y).0;
+        }
+
+        let (width, height) = colors.dimensions();
+        let mut sum = [0.0f32; 3];
+        let mut total_weight = 0.0;
+        for &(dx, dy, weight) in &self.taps {
+            let (Some(x), Some(y)) = (x.checked_add_signed(dx), y.checked_add_signed(dy)) else {
+                continue;
+            };
+            if x >= width || y >= height {
+                continue;
+            }
+            for (sum, &channel) in sum.iter_mut().zip(&colors.get_pixel(x, y).0) {
+                *sum += weight * srgb_to_linear(channel);
+            }
+            total_weight += weight;
+        }
+
+        if total_weight <= 0.0 {
+            return colors.get_pixel(x, y).0;
+        }
+        sum.map(|sum| linear_to_srgb((sum / total_weight).clamp(0.0, 1.0)))
+    }
+
+    /// Filter the colors of the sub-samples of pixel `(x, y)`, whose center is `point`.
+    ///
+    /// All sub-samples are colored at once by `colors`, so that they can be computed in batches.
+    pub fn reconstruct<T, F>(&self, x: u32, y: u32, point: Complex<T>, colors: F) -> Srgb
+    where
+        T: Real,
+        F: Fn(&[Complex<T>]) -> Vec<Srgb>,
+    {
+        let grid = self.grid;
+        let cell_size = 2.0 * self.radius / f64::from(grid);
+
+        let (weights, points): (Vec<f32>, Vec<Complex<T>>) = (0..grid * grid)
+            .filter_map(|cell| {
+                let (u, v) = jitter(x, y, cell);
+                let u = (f64::from(cell % grid) + u) * cell_size - self.radius;
+                let v = (f64::from(cell / grid) + v) * cell_size - self.radius;
+
+                let weight = self.filter.weight(u, v, self.radius) as f32;
+                let offset = Complex::new(
+                    T::from_f64(u * self.pixel.re),
+                    T::from_f64(v * self.pixel.im),
+                );
+                let sample = point + offset;
+                (weight != 0.0).then_some((weight, sample))
+            })
+            .unzip();
+
+        let mut sum = [0.0f32; 3];
+        for (weight, sample) in weights.iter().zip(colors(&points)) {
+            for (sum, channel) in sum.iter_mut().zip(sample) {
+                *sum += weight * srgb_to_linear(channel);
+            }
+        }
+        let total_weight: f32 = weights.iter().sum();
+
+        // Negative lobes may cancel out all weight in degenerate cases.
+        if total_weight <= 0.0 {
+            return colors(&[point])[0];
+        }
+
+        sum.map(|sum| linear_to_srgb((sum / total_weight).clamp(0.0, 1.0)))
+    }
+
+    /// Quantize the color of pixel `(x, y)`, dithering if configured.
+    pub fn quantize(&self, x: u32, y: u32, color: Srgb) -> Rgb<u8> {
+        let noise = if self.dither {
+            // Triangular noise with an amplitude of one quantization step.
+            let (a, b) = jitter(x, y, u32::MAX);
+            (a + b - 1.0) as f32
+        } else {
+            0.0
+        };
+
+        Rgb(color.map(|channel| (channel * 255.0 + noise).round().clamp(0.0, 255.0) as u8))
+    }
+}
+
+/// Pseudo-random offset in [0, 1)² for sub-sample `cell` of pixel `(x, y)`.
+///
+/// Hashing the coordinates keeps renders reproducible regardless of the
+/// order in which pixels are rendered.
+fn jitter(x: u32, y: u32, cell: u32) -> (f64, f64) {
+    // SplitMix64 finalizer
+    let mut h =
+        (u64::from(x) << 32 | u64::from(y)) ^ u64::from(cell).wrapping_mul(0x9e3779b97f4a7c15);
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d049bb133111eb);
+    h ^= h >> 31;
+
+    let unit = |bits: u64| bits as f64 / (1u64 << 32) as f64;
+    (unit(h >> 32), unit(h & u64::from(u32::MAX)))
+}
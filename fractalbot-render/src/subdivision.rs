@@ -0,0 +1,301 @@
+use image::{Luma, Rgb};
+use log::debug;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    bounding_box::BoundingBox,
+    color::Srgb,
+    distance_estimation::{DistanceEstimation, Orbit},
+    float::Real,
+    progress::Progress,
+    sampling::{Colors, Distances},
+    symmetry::GridSymmetry,
+};
+
+/// Side length of the square blocks of pixels subdivided in parallel.
+const BLOCK: u32 = 64;
+
+/// Rectangles with fewer pixels than this along a side are not subdivided any further.
+const MIN_SIDE: u32 = 8;
+
+/// Sample the pixels of an image spanning `bbx`, writing their `distances`
+/// and their colors (as picked by `color`) to `colors`, but skip uniform parts
+/// of the interior of a connected set (Mariani–Silver subdivision).
+///
+/// The image is split into blocks, which are subdivided recursively: the border of a
+/// rectangle is estimated first, and if every pixel on it lies in the interior
+/// (distance ≤ 0), the whole rectangle is filled as interior. Otherwise, the rectangle
+/// is split in two along its longer side. Since the filled Julia set has no holes, an
+/// exterior point inside the rectangle is joined to infinity by a path crossing the
+/// border, but the border is only sampled at pixels: an exterior filament thinner than
+/// a pixel may pass between two of them, and is then filled over.
+///
+/// Filled pixels get a [default](Orbit::default) orbit, which has distance 0 but
+/// tells nothing else. In particular, they are never refined, so such errors are kept
+/// in the image. Only meaningful for connected sets, since disconnected ones
+/// have no interior.
+/// Blocks consisting of mirror images only are skipped; like
+/// [GridSymmetry::is_canonical] pixels in general, they are left to the caller.
+pub(crate) fn sample<T, F>(
+    julia: &DistanceEstimation<T>,
+    bbx: &BoundingBox<T>,
+    symmetry: GridSymmetry,
+    progress: &Progress,
+    distances: &mut Distances,
+    colors: &mut Colors,
+    color: F,
+) where
+    T: Real,
+    F: Fn(&Orbit) -> Srgb + Sync,
+{
+    let (width, height) = distances.dimensions();
+    let blocks: Vec<_> = (0..height)
+        .step_by(BLOCK as usize)
+        .flat_map(|y| (0..width).step_by(BLOCK as usize).map(move |x| (x, y)))
+        .collect();
+
+    let blocks: Vec<Block<T>> = blocks
+        .into_par_iter()
+        .map(|(x, y)| {
+            let mut block = Block::new(julia, bbx, width, height, (x, y));
+            if block.is_canonical(symmetry) {
+                block.subdivide(0, 0, block.width - 1, block.height - 1);
+            }
+            progress.advance(block.orbits.len() as u64);
+            block
+        })
+        .collect();
+
+    let mut filled = 0;
+    for block in blocks {
+        filled += block.filled;
+        let (x_origin, y_origin) = block.origin;
+        for (index, orbit) in block.orbits.iter().enumerate() {
+            let (x, y) = block.coordinates(index);
+            let (x, y) = (x_origin + x, y_origin + y);
+            distances.put_pixel(x, y, Luma([orbit.distance]));
+            colors.put_pixel(x, y, Rgb(color(orbit)));
+        }
+    }
+    debug!("Filled {filled} interior pixels without estimating them");
+}
+
+/// A block of pixels of an image, together with the orbits iterated so far.
+struct Block<'a, T> {
+    julia: &'a DistanceEstimation<T>,
+    bbx: &'a BoundingBox<T>,
+    /// Size of the whole image.
+    image: (u32, u32),
+    /// Coordinates of the top left pixel of the block in the image.
+    origin: (u32, u32),
+    width: u32,
+    height: u32,
+    /// Orbits of the pixels of the block, in row-major order.
+    orbits: Vec<Orbit>,
+    known: Vec<bool>,
+    /// Number of pixels filled without estimating them.
+    filled: usize,
+}
+
+impl<'a, T: Real> Block<'a, T> {
+    fn new(
+        julia: &'a DistanceEstimation<T>,
+        bbx: &'a BoundingBox<T>,
+        image_width: u32,
+        image_height: u32,
+        origin: (u32, u32),
+    ) -> Self {
+        let (x, y) = origin;
+        let width = BLOCK.min(image_width - x);
+        let height = BLOCK.min(image_height - y);
+        let pixels = width as usize * height as usize;
+
+        Self {
+            julia,
+            bbx,
+            image: (image_width, image_height),
+            origin,
+            width,
+            height,
+            orbits: vec![Orbit::default(); pixels],
+            known: vec![false; pixels],
+            filled: 0,
+        }
+    }
+
+    /// Whether any pixel of the block has to be rendered.
+    fn is_canonical(&self, symmetry: GridSymmetry) -> bool {
+        let (x_origin, y_origin) = self.origin;
+        (0..self.height)
+            .any(|y| (0..self.width).any(|x| symmetry.is_canonical(x_origin + x, y_origin + y)))
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    fn coordinates(&self, index: usize) -> (u32, u32) {
+        let width = self.width as usize;
+        ((index % width) as u32, (index / width) as u32)
+    }
+
+    /// Iterate those of the given pixels that are not known yet.
+    fn estimate(&mut self, pixels: impl Iterator<Item = (u32, u32)>) {
+        let (x_origin, y_origin) = self.origin;
+        let (width, height) = self.image;
+
+        let (indices, points): (Vec<_>, Vec<_>) = pixels
+            .map(|(x, y)| (self.index(x, y), (x, y)))
+            .filter(|&(index, _)| !self.known[index])
+            .map(|(index, (x, y))| {
+                let point = self
+                    .bbx
+                    .point_from_grid(x_origin + x, y_origin + y, width, height);
+                (index, point)
+            })
+            .unzip();
+
+        let mut orbits = vec![Orbit::default(); points.len()];
+        self.julia.orbits(&points, &mut orbits);
+        for (index, orbit) in indices.into_iter().zip(orbits) {
+            self.orbits[index] = orbit;
+            self.known[index] = true;
+        }
+    }
+
+    /// Estimate or fill all pixels of the rectangle with corners `(x_min, y_min)`
+    /// and `(x_max, y_max)` (inclusive).
+    fn subdivide(&mut self, x_min: u32, y_min: u32, x_max: u32, y_max: u32) {
+        let rectangle =
+            move || (y_min..=y_max).flat_map(move |y| (x_min..=x_max).map(move |x| (x, y)));
+        let border = move || {
+            rectangle().filter(move |&(x, y)| x == x_min || x == x_max || y == y_min || y == y_max)
+        };
+
+        self.estimate(border());
+
+        if x_max - x_min < 2 || y_max - y_min < 2 {
+            // The border is all there is.
+            return;
+        }
+
+        if border().all(|(x, y)| self.orbits[self.index(x, y)].distance <= 0.0) {
+            for (x, y) in rectangle() {
+                let index = self.index(x, y);
+                if !self.known[index] {
+                    self.orbits[index] = Orbit::default();
+                    self.known[index] = true;
+                    self.filled += 1;
+                }
+            }
+            return;
+        }
+
+        if x_max - x_min < MIN_SIDE || y_max - y_min < MIN_SIDE {
+            self.estimate(rectangle());
+            return;
+        }
+
+        if x_max - x_min >= y_max - y_min {
+            let x_mid = x_min + (x_max - x_min) / 2;
+            self.subdivide(x_min, y_min, x_mid, y_max);
+            self.subdivide(x_mid, y_min, x_max, y_max);
+        } else {
+            let y_mid = y_min + (y_max - y_min) / 2;
+            self.subdivide(x_min, y_min, x_max, y_mid);
+            self.subdivide(x_min, y_mid, x_max, y_max);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::RAINBOW,
+        coloring::{Colorizer, DistanceColorizer},
+        complex::Complex,
+    };
+
+    /// Connected parameters: the basilica, the rabbit, and one close to the parabolic
+    /// point 1/4, whose Julia set has thin interior parts between wide fjords.
+    const CONNECTED: [Complex; 3] = [
+        Complex::new(-1.0, 0.0),
+        Complex::new(-0.123, 0.745),
+        Complex::new(0.24, 0.0),
+    ];
+
+    /// Estimate every pixel of a `width`×`height` image spanning `bbx`, like the renderer
+    /// does without subdivision.
+    fn sample_all(
+        julia: &DistanceEstimation<f64>,
+        bbx: &BoundingBox,
+        width: u32,
+        height: u32,
+        color: impl Fn(&Orbit) -> Srgb,
+    ) -> (Distances, Colors) {
+        let points: Vec<_> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| bbx.point_from_grid(x, y, width, height)))
+            .collect();
+        let mut orbits = vec![Orbit::default(); points.len()];
+        julia.orbits(&points, &mut orbits);
+
+        let mut distances = Distances::new(width, height);
+        let mut colors = Colors::new(width, height);
+        for ((distance, pixel), orbit) in
+            distances.pixels_mut().zip(colors.pixels_mut()).zip(&orbits)
+        {
+            *distance = Luma([orbit.distance]);
+            *pixel = Rgb(color(orbit));
+        }
+        (distances, colors)
+    }
+
+    #[test]
+    fn subdivision_matches_estimating_every_pixel() {
+        let (width, height) = (256, 192);
+        let bbx: BoundingBox = [Complex::new(-1.6, -1.2), Complex::new(1.6, 1.2)]
+            .into_iter()
+            .collect();
+        let colorizer = DistanceColorizer::new(true);
+        let color = |orbit: &Orbit| {
+            if orbit.distance <= 0.0 {
+                [0.0; 3]
+            } else {
+                colorizer.color(orbit, &RAINBOW)
+            }
+        };
+
+        for c in CONNECTED {
+            let julia = DistanceEstimation::<f64>::new(c, 1024);
+            assert!(julia.is_connected(), "c = {c} is not connected");
+
+            let (expected_distances, expected_colors) =
+                sample_all(&julia, &bbx, width, height, color);
+            let mut distances = Distances::new(width, height);
+            let mut colors = Colors::new(width, height);
+            sample(
+                &julia,
+                &bbx,
+                GridSymmetry::none(width, height),
+                &Progress::hidden(),
+                &mut distances,
+                &mut colors,
+                color,
+            );
+
+            for (x, y, expected) in expected_distances.enumerate_pixels() {
+                assert_eq!(
+                    distances.get_pixel(x, y).0[0].to_bits(),
+                    expected.0[0].to_bits(),
+                    "distance of pixel ({x}, {y}) for c = {c}"
+                );
+                assert_eq!(
+                    colors.get_pixel(x, y),
+                    expected_colors.get_pixel(x, y),
+                    "color of pixel ({x}, {y}) for c = {c}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use image::{ImageBuffer, Pixel};
+
+/// The point reflection z → -z on the pixel grid of an image.
+///
+/// Every quadratic Julia set is symmetric under this reflection, since `f(-z) = f(z)`.
+/// Where an image overlaps its mirror image, only one pixel of each mirrored
+/// pair needs to be rendered.
+/// See [crate::bounding_box::BoundingBox::snap_symmetric] to obtain a symmetry for an image.
+#[derive(Debug, Clone, Copy)]
+pub struct GridSymmetry {
+    /// Pixel (x, y) is mirrored to (a - x, b - y).
+    a: u32,
+    b: u32,
+    width: u32,
+    height: u32,
+}
+
+impl GridSymmetry {
+    pub(crate) fn new(a: u32, b: u32, width: u32, height: u32) -> Self {
+        Self {
+            a,
+            b,
+            width,
+            height,
+        }
+    }
+
+    /// The symmetry of an image that does not overlap its mirror image.
+    pub fn none(width: u32, height: u32) -> Self {
+        // No pixel (x, y) with x < width has a mirror a - x < width.
+        Self::new(2 * width, 2 * height, width, height)
+    }
+
+    /// The pixel showing -z, if pixel `(x, y)` shows z and the image contains -z.
+    pub fn mirror(&self, x: u32, y: u32) -> Option<(u32, u32)> {
+        let x = self.a.checked_sub(x).filter(|&x| x < self.width)?;
+        let y = self.b.checked_sub(y).filter(|&y| y < self.height)?;
+        Some((x, y))
+    }
+
+    /// Whether pixel `(x, y)` has to be rendered, i.e. it has no mirror image,
+    /// or it comes before its mirror image in row-major order.
+    pub fn is_canonical(&self, x: u32, y: u32) -> bool {
+        match self.mirror(x, y) {
+            Some((mx, my)) => (y, x) <= (my, mx),
+            None => true,
+        }
+    }
+
+    /// Fill in all pixels that are not canonical by copying their mirror images.
+    pub fn mirror_image<P>(&self, image: &mut ImageBuffer<P, Vec<P::Subpixel>>)
+    where
+        P: Pixel,
+    {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if let Some((mx, my)) = self.mirror(x, y)
+                    && (my, mx) < (y, x)
+                {
+                    let pixel = *image.get_pixel(mx, my);
+                    image.put_pixel(x, y, pixel);
+                }
+            }
+        }
+    }
+}
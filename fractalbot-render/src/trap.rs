@@ -0,0 +1,225 @@
+use std::f64::consts::PI;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use anyhow::{Error, bail};
+use rand::{Rng, RngExt};
+use rand_distr::Distribution;
+
+use crate::{
+    complex::Complex,
+    float::{Lanes, Real},
+};
+
+/// A shape in the plane that orbits are measured against: the closer an orbit
+/// comes to the trap, the more it is "caught" (see [Orbit::trap](crate::distance_estimation::Orbit::trap)).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Trap<T = f64> {
+    Point {
+        center: Complex<T>,
+    },
+    /// The line through `point` along the unit vector `direction`.
+    Line {
+        point: Complex<T>,
+        direction: Complex<T>,
+    },
+    Circle {
+        center: Complex<T>,
+        radius: T,
+    },
+    /// The horizontal and the vertical line through `center`.
+    Cross {
+        center: Complex<T>,
+    },
+}
+
+impl Default for Trap {
+    fn default() -> Self {
+        Self::Point {
+            center: Complex::new(0.0, 0.0),
+        }
+    }
+}
+
+impl<T: Real> Trap<T> {
+    /// The same trap in precision `U`.
+    pub fn cast<U: Real>(&self) -> Trap<U> {
+        let cast = |z: Complex<T>| Complex::new(U::from_f64(z.re.into()), U::from_f64(z.im.into()));
+        match *self {
+            Self::Point { center } => Trap::Point {
+                center: cast(center),
+            },
+            Self::Line { point, direction } => Trap::Line {
+                point: cast(point),
+                direction: cast(direction),
+            },
+            Self::Circle { center, radius } => Trap::Circle {
+                center: cast(center),
+                radius: U::from_f64(radius.into()),
+            },
+            Self::Cross { center } => Trap::Cross {
+                center: cast(center),
+            },
+        }
+    }
+
+    fn anchor(&self) -> Complex<T> {
+        match *self {
+            Self::Point { center } | Self::Circle { center, .. } | Self::Cross { center } => center,
+            Self::Line { point, .. } => point,
+        }
+    }
+
+    /// Distance of `z` to the trap.
+    pub fn distance(&self, z: Complex<T>) -> T {
+        let d = z - self.anchor();
+        match *self {
+            Self::Point { .. } => (d.re * d.re + d.im * d.im).sqrt(),
+            Self::Line { direction, .. } => (d.re * direction.im - d.im * direction.re).abs(),
+            Self::Circle { radius, .. } => ((d.re * d.re + d.im * d.im).sqrt() - radius).abs(),
+            Self::Cross { .. } => d.re.abs().min(d.im.abs()),
+        }
+    }
+
+    /// Like [Trap::distance], for the points with real parts `re` and imaginary
+    /// parts `im`, performing the same operations.
+    pub(crate) fn distance_lanes(&self, re: T::Lanes, im: T::Lanes) -> T::Lanes {
+        let splat = <T::Lanes as Lanes<T>>::splat;
+
+        let anchor = self.anchor();
+        let (d_re, d_im) = (re - splat(anchor.re), im - splat(anchor.im));
+        match *self {
+            Self::Point { .. } => (d_re * d_re + d_im * d_im).sqrt(),
+            Self::Line { direction, .. } => {
+                (d_re * splat(direction.im) - d_im * splat(direction.re)).abs()
+            }
+            Self::Circle { radius, .. } => {
+                ((d_re * d_re + d_im * d_im).sqrt() - splat(radius)).abs()
+            }
+            Self::Cross { .. } => d_re.abs().min(d_im.abs()),
+        }
+    }
+}
+
+impl Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Point { center } => write!(f, "point {center}"),
+            Self::Line { point, direction } => write!(
+                f,
+                "line through {point} at {:.0}°",
+                direction.arg().to_degrees()
+            ),
+            Self::Circle { center, radius } => {
+                write!(f, "circle around {center} of radius {radius:.2}")
+            }
+            Self::Cross { center } => write!(f, "cross at {center}"),
+        }
+    }
+}
+
+/// Shapes of [Trap]s.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TrapShape {
+    Point,
+    Line,
+    Circle,
+    Cross,
+}
+
+impl TrapShape {
+    pub const NAMES: [&str; 4] = ["point", "line", "circle", "cross"];
+    const ALL: [Self; 4] = [Self::Point, Self::Line, Self::Circle, Self::Cross];
+}
+
+impl FromStr for TrapShape {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "point" => Ok(Self::Point),
+            "line" => Ok(Self::Line),
+            "circle" => Ok(Self::Circle),
+            "cross" => Ok(Self::Cross),
+            _ => bail!(
+                "Unknown trap {s:?}, expected one of {names} or random",
+                names = Self::NAMES.join(", ")
+            ),
+        }
+    }
+}
+
+impl Display for TrapShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Point => "point",
+            Self::Line => "line",
+            Self::Circle => "circle",
+            Self::Cross => "cross",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Places traps of a shape randomly, near the orbits of points close to a Julia set.
+impl Distribution<Trap> for TrapShape {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Trap {
+        let center = Complex::new(rng.random_range(-1.0..1.0), rng.random_range(-1.0..1.0));
+        match self {
+            Self::Point => Trap::Point { center },
+            Self::Line => Trap::Line {
+                point: center,
+                direction: Complex::from_polar(1.0, rng.random_range(0.0..PI)),
+            },
+            Self::Circle => Trap::Circle {
+                center,
+                radius: rng.random_range(0.2..1.0),
+            },
+            Self::Cross => Trap::Cross { center },
+        }
+    }
+}
+
+/// How to pick the trap of a render: a fixed trap, a random one of a given
+/// shape, or a random one of any shape.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum TrapChoice {
+    Fixed(Trap),
+    Shape(TrapShape),
+    #[default]
+    Random,
+}
+
+impl FromStr for TrapChoice {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("random") {
+            return Ok(Self::Random);
+        }
+        s.parse().map(Self::Shape)
+    }
+}
+
+impl Display for TrapChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fixed(trap) => trap.fmt(f),
+            Self::Shape(shape) => shape.fmt(f),
+            Self::Random => f.write_str("random"),
+        }
+    }
+}
+
+impl Distribution<Trap> for TrapChoice {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Trap {
+        match self {
+            Self::Fixed(trap) => *trap,
+            Self::Shape(shape) => shape.sample(rng),
+            Self::Random => {
+                let shape = TrapShape::ALL[rng.random_range(0..TrapShape::ALL.len())];
+                shape.sample(rng)
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use fractalbot_post::auth::{self, App};
+use log::info;
+
+use crate::{
+    env::{AuthAction, Environment, Login, Register},
+    user_agent,
+};
+
+/// Write a file only readable by the current user.
+fn write_secret(path: &Path, contents: &str) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Store the application and its authorization URL as `key=value` lines.
+fn write_app(path: &Path, app: &App, url: &str) -> Result<()> {
+    let contents = format!(
+        "client_id={}\nclient_secret={}\nauthorize_url={url}\n",
+        app.client_id, app.client_secret
+    );
+    write_secret(path, &contents)
+}
+
+fn read_app(path: &Path) -> Result<(App, String)> {
+    let contents = fs::read_to_string(path).with_context(|| {
+        format!(
+            "Failed to read application from {} (run `auth register` first)",
+            path.display()
+        )
+    })?;
+
+    let field = |key: &str| -> Result<String> {
+        contents
+            .lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+            .map(String::from)
+            .with_context(|| format!("{key} missing from {}", path.display()))
+    };
+
+    let app = App {
+        client_id: field("client_id")?,
+        client_secret: field("client_secret")?,
+    };
+    Ok((app, field("authorize_url")?))
+}
+
+fn register(options: Register) -> Result<()> {
+    let instance_url = Environment::instance_url()?;
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let (app, url) = rt.block_on(auth::register_app(
+        instance_url,
+        options.name,
+        options.website,
+        user_agent(),
+    ))?;
+
+    write_app(&options.app, &app, &url)?;
+    info!("Stored application in {}", options.app.display());
+
+    println!("Log in as the bot's account, open the following URL and authorize the application:");
+    println!();
+    println!("    {url}");
+    println!();
+    println!("Then run `fractalbot auth login` and enter the authorization code.");
+    Ok(())
+}
+
+fn prompt_code(url: &str) -> Result<String> {
+    println!("Open the following URL as the bot's account and authorize the application:");
+    println!();
+    println!("    {url}");
+    println!();
+    print!("Authorization code: ");
+    io::stdout().flush()?;
+
+    let mut code = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut code)
+        .context("Failed to read authorization code")?;
+    Ok(code.trim().into())
+}
+
+fn login(options: Login) -> Result<()> {
+    let instance_url = Environment::instance_url()?;
+    let (app, url) = read_app(&options.app)?;
+
+    let code = match options.code {
+        Some(code) => code,
+        None => prompt_code(&url)?,
+    };
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let access_token = rt.block_on(auth::fetch_access_token(
+        instance_url.clone(),
+        app,
+        code,
+        user_agent(),
+    ))?;
+
+    let client = fractalbot_post::Client::new(instance_url, access_token.clone(), user_agent())
+        .context("Failed to initialize Mastodon client")?;
+    let account = rt.block_on(client.verify_credentials())?;
+    info!("Logged in as {account}");
+
+    write_secret(&options.credentials, &access_token)?;
+    info!("Stored access token in {}", options.credentials.display());
+    Ok(())
+}
+
+pub fn auth(action: AuthAction) -> Result<()> {
+    match action {
+        AuthAction::Register(options) => register(options),
+        AuthAction::Login(options) => login(options),
+    }
+}
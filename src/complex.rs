@@ -1 +0,0 @@
-pub type Complex = num_complex::Complex<f64>;
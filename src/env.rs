@@ -1,11 +1,25 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use argh::FromArgs;
 use fractalbot_post::StatusVisibility;
 
 use std::env;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use crate::complex::Complex;
+use fractalbot_render::{
+    MAX_RADIUS, RenderRequest, Sampling,
+    color::PaletteChoice,
+    coloring::{Coloring, Interior},
+    complex::Complex,
+    filter::Filter,
+    precision::Precision,
+    trap::TrapChoice,
+};
+
+/// Name of the file `auth login` writes the access token to,
+/// and of the credential read from systemd's `$CREDENTIALS_DIRECTORY`.
+pub const ACCESS_TOKEN_CREDENTIAL: &str = "mastodon-access-token";
 
 #[derive(Debug)]
 pub struct Environment {
@@ -15,23 +29,48 @@ pub struct Environment {
 
 impl Environment {
     pub fn from_env() -> Result<Self> {
-        let instance_url =
-            env::var("MASTODON_INSTANCE_URL").context("MASTODON_INSTANCE_URL not set")?;
-        let access_token =
-            env::var("MASTODON_ACCESS_TOKEN").context("MASTODON_ACCESS_TOKEN not set")?;
-
         Ok(Self {
-            instance_url,
-            access_token,
+            instance_url: Self::instance_url()?,
+            access_token: Self::access_token()?,
         })
     }
+
+    pub fn instance_url() -> Result<String> {
+        env::var("MASTODON_INSTANCE_URL").context("MASTODON_INSTANCE_URL not set")
+    }
+
+    /// Read the access token from `MASTODON_ACCESS_TOKEN`, the file named by
+    /// `MASTODON_ACCESS_TOKEN_FILE`, or the credential in `$CREDENTIALS_DIRECTORY`,
+    /// whichever is found first.
+    fn access_token() -> Result<String> {
+        if let Ok(access_token) = env::var("MASTODON_ACCESS_TOKEN") {
+            return Ok(access_token);
+        }
+
+        let path = if let Some(path) = env::var_os("MASTODON_ACCESS_TOKEN_FILE") {
+            PathBuf::from(path)
+        } else if let Some(dir) = env::var_os("CREDENTIALS_DIRECTORY") {
+            Path::new(&dir).join(ACCESS_TOKEN_CREDENTIAL)
+        } else {
+            bail!(
+                "MASTODON_ACCESS_TOKEN not set (neither are MASTODON_ACCESS_TOKEN_FILE or CREDENTIALS_DIRECTORY)"
+            )
+        };
+
+        let access_token = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read access token from {}", path.display()))?;
+        Ok(access_token.trim().into())
+    }
 }
 
 #[derive(FromArgs, PartialEq, Debug)]
 #[argh(subcommand)]
 pub enum Action {
     Save(Save),
+    Tiles(Tiles),
     Post(Post),
+    Reply(Reply),
+    Auth(Auth),
 }
 
 #[derive(FromArgs, PartialEq, Debug)]
@@ -41,6 +80,36 @@ pub struct Save {
     #[argh(positional, default = r#""fractal.png".into()"#)]
     /// path to the image on disk
     pub path: PathBuf,
+
+    #[argh(option, default = "fractalbot_render::WIDTH", from_str_fn(parse_size))]
+    /// maximum width of the image
+    pub width: u32,
+
+    #[argh(option, default = "fractalbot_render::HEIGHT", from_str_fn(parse_size))]
+    /// maximum height of the image
+    pub height: u32,
+
+    #[argh(switch)]
+    /// render and encode the image in bands of rows, keeping memory use
+    /// bounded for very large images
+    pub stream: bool,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Render a Deep Zoom (DZI) tile pyramid for viewing in a browser.
+#[argh(subcommand, name = "tiles")]
+pub struct Tiles {
+    #[argh(positional, default = r#""fractal.dzi".into()"#)]
+    /// path to the descriptor; tiles are written next to it (to fractal_files/)
+    pub path: PathBuf,
+
+    #[argh(option, default = "16384", from_str_fn(parse_size))]
+    /// maximum width of the full-resolution image
+    pub width: u32,
+
+    #[argh(option, default = "16384", from_str_fn(parse_size))]
+    /// maximum height of the full-resolution image
+    pub height: u32,
 }
 
 #[derive(FromArgs, PartialEq, Debug)]
@@ -50,6 +119,112 @@ pub struct Post {
     #[argh(option, default = "StatusVisibility::Private")]
     /// visibility of the status (public, unlisted, private or direct)
     pub status_visibility: StatusVisibility,
+
+    #[argh(switch)]
+    /// reply with a poll letting followers choose tomorrow's palette or region
+    pub poll: bool,
+
+    #[argh(option, default = r#""poll.state".into()"#)]
+    /// file linking the last poll to the next run
+    pub poll_state: PathBuf,
+
+    #[argh(switch)]
+    /// also use the image as the account's avatar and header
+    pub update_profile: bool,
+
+    #[argh(option)]
+    /// account (user or user@instance) to send a direct message to if the run fails
+    pub notify_admin: Option<String>,
+
+    #[argh(option)]
+    /// webhook to post to if the run fails and the admin could not be notified
+    pub notify_webhook: Option<String>,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Answer mentions of the bot's account with custom renders.
+#[argh(subcommand, name = "reply")]
+pub struct Reply {
+    #[argh(option, default = "60")]
+    /// seconds to wait between polling for new mentions
+    pub poll_interval: u64,
+
+    #[argh(option, default = "2")]
+    /// maximum number of renders running at the same time
+    pub max_concurrent: usize,
+
+    #[argh(option, default = "10")]
+    /// maximum number of accepted requests waiting for or being rendered
+    pub max_queued: usize,
+
+    #[argh(option, default = "3")]
+    /// maximum number of renders per account within the rate limit window
+    pub rate_limit: usize,
+
+    #[argh(option, default = "3600")]
+    /// length of the rate limit window in seconds
+    pub rate_limit_window: u64,
+
+    #[argh(option)]
+    /// only answer to this account (user or user@instance); can be repeated
+    pub allow: Vec<String>,
+
+    #[argh(option)]
+    /// never answer to this account (user or user@instance); can be repeated
+    pub deny: Vec<String>,
+
+    #[argh(switch)]
+    /// handle pending mentions once and exit instead of polling forever
+    pub once: bool,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Set up access to the account at MASTODON_INSTANCE_URL.
+#[argh(subcommand, name = "auth")]
+pub struct Auth {
+    #[argh(subcommand)]
+    pub action: AuthAction,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+pub enum AuthAction {
+    Register(Register),
+    Login(Login),
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Register the bot as an application with the instance.
+#[argh(subcommand, name = "register")]
+pub struct Register {
+    #[argh(option, default = r#""mastodon-app".into()"#)]
+    /// file to store the application's client ID and secret in
+    pub app: PathBuf,
+
+    #[argh(option, default = r#""fractalbot".into()"#)]
+    /// name of the application shown to users of the instance
+    pub name: String,
+
+    #[argh(option)]
+    /// website of the application
+    pub website: Option<String>,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Authorize the registered application and store an access token.
+#[argh(subcommand, name = "login")]
+pub struct Login {
+    #[argh(option, default = r#""mastodon-app".into()"#)]
+    /// file containing the application registered by `auth register`
+    pub app: PathBuf,
+
+    #[argh(option)]
+    /// authorization code (asked for interactively if not given)
+    pub code: Option<String>,
+
+    #[argh(option, default = "ACCESS_TOKEN_CREDENTIAL.into()")]
+    /// file to write the access token to
+    pub credentials: PathBuf,
 }
 
 #[derive(FromArgs, PartialEq, Debug)]
@@ -59,6 +234,159 @@ pub struct Cmdline {
     /// complex number c parametrizing the generating polynomial f(z) = z² + c
     pub parameter: Option<Complex>,
 
+    #[argh(switch)]
+    /// render the Mandelbrot set instead of a Julia set (ignoring the parameter)
+    pub mandelbrot: bool,
+
+    #[argh(option, default = "PaletteChoice::default()")]
+    /// color palette: one of rainbow, whites, arctic, citrus, dusk, pink, glow,
+    /// or a random palette from a family (presets, phase-shift, monotone)
+    pub palette: PaletteChoice,
+
+    #[argh(option, default = "fractalbot_render::SAMPLES")]
+    /// number of samples per pixel, spent only near the boundary of the set and
+    /// at sharp edges, spread over the support of the filter (rounded down to a
+    /// square; 1 disables supersampling)
+    pub samples: u32,
+
+    #[argh(option, default = "Filter::default()")]
+    /// filter weighting the samples of a pixel: one of box, tent, mitchell,
+    /// lanczos, gaussian
+    pub filter: Filter,
+
+    #[argh(option, from_str_fn(parse_radius))]
+    /// radius of the filter in pixels, at most 4 (defaults to 0.5 for box, 1 for
+    /// tent, 2 for mitchell, 3 for lanczos and 1.5 for gaussian)
+    pub filter_radius: Option<f64>,
+
+    #[argh(option, default = "Coloring::default()")]
+    /// what picks colors from the palette: distance (to the set), iterations
+    /// (smooth iteration count of escaping points, in bands), blend (of both),
+    /// trap (closeness of orbits to a trap), the average of stripes or
+    /// curvature along orbits, or the argument of escaping points, by binary
+    /// decomposition (binary) or with field lines (field-lines)
+    pub coloring: Coloring,
+
+    #[argh(option, default = "0.5")]
+    /// weight of the iteration count against the distance when blending,
+    /// between 0 and 1
+    pub blend: f64,
+
+    #[argh(option, default = "TrapChoice::default()")]
+    /// trap for trap coloring: one of point, line, circle, cross (placed
+    /// randomly), or random (of any shape)
+    pub trap: TrapChoice,
+
+    #[argh(switch)]
+    /// shade trap coloring by the distance to the set
+    pub shade: bool,
+
+    #[argh(option, from_str_fn(parse_frequency))]
+    /// frequency of the stripes of stripe coloring, at least 1 (random if not given)
+    pub stripes: Option<u32>,
+
+    #[argh(option)]
+    /// weight of the average against the distance in stripe and curvature
+    /// coloring, between 0 and 1 (random if not given)
+    pub mix: Option<f64>,
+
+    #[argh(switch)]
+    /// darken every other equipotential band in binary and field-lines coloring
+    pub bands: bool,
+
+    #[argh(option)]
+    /// color the interior of connected sets (black if not given) by how orbits
+    /// approach the attracting cycle: convergence (speed), distance (to the
+    /// Julia set, estimated from the cycle), angle (of approach) or cycle
+    /// (point converged to)
+    pub interior: Option<Interior>,
+
+    #[argh(switch)]
+    /// shade colors as if the outside of the set were a relief rising towards it
+    pub relief: bool,
+
+    #[argh(option)]
+    /// angle the relief is lit from, in degrees counterclockwise from the right
+    /// (random if not given)
+    pub light: Option<f64>,
+
+    #[argh(switch)]
+    /// dither colors to avoid banding in smooth gradients
+    pub dither: bool,
+
+    #[argh(option)]
+    /// precision to iterate points in: single or double (by default the lowest
+    /// precision resolving the pixels, usually single for whole sets)
+    pub precision: Option<Precision>,
+
+    #[argh(switch)]
+    /// skip the insides of interior regions of connected sets, by subdividing
+    /// the image into rectangles whose borders lie in the interior
+    pub subdivide: bool,
+
+    #[argh(option)]
+    /// seconds rendering may take; if a quick preview suggests it would take
+    /// longer, iterations, samples and size are reduced, or another parameter is chosen
+    pub budget: Option<u64>,
+
     #[argh(subcommand)]
     pub action: Action,
 }
+
+impl Cmdline {
+    pub fn render_request(&self) -> RenderRequest {
+        RenderRequest {
+            parameter: self.parameter,
+            palette: self.palette,
+            sampling: Sampling {
+                samples: self.samples,
+                filter: self.filter,
+                radius: self.filter_radius,
+                dither: self.dither,
+                precision: self.precision,
+                subdivide: self.subdivide,
+                coloring: self.coloring,
+                blend: self.blend,
+                shade: self.shade,
+                bands: self.bands,
+                relief: self.relief,
+                interior: self.interior,
+                mandelbrot: self.mandelbrot,
+                // Sampled from `trap`, `stripes`, `mix` and `light` when planning the render.
+                ..Default::default()
+            },
+            trap: self.trap,
+            stripes: self.stripes,
+            mix: self.mix,
+            light: self.light,
+            budget: self.budget.map(Duration::from_secs),
+            ..Default::default()
+        }
+    }
+}
+
+fn parse_size(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(0) => Err("Size must be at least 1 pixel".into()),
+        Ok(size) => Ok(size),
+        Err(err) => Err(format!("Invalid size {value:?}: {err}")),
+    }
+}
+
+fn parse_frequency(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(0) => Err("Frequency of stripes must be at least 1".into()),
+        Ok(frequency) => Ok(frequency),
+        Err(err) => Err(format!("Invalid frequency {value:?}: {err}")),
+    }
+}
+
+fn parse_radius(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(radius) if radius > 0.0 && radius <= MAX_RADIUS => Ok(radius),
+        Ok(_) => Err(format!(
+            "Filter radius must be positive and at most {MAX_RADIUS}, got {value}"
+        )),
+        Err(err) => Err(format!("Invalid filter radius {value:?}: {err}")),
+    }
+}
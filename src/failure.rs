@@ -0,0 +1,136 @@
+use std::any::Any;
+use std::fmt::{self, Display};
+use std::time::Duration;
+
+use anyhow::{Error, Result};
+use log::{info, warn};
+
+use fractalbot_render::{
+    average::Average, color::PaletteChoice, complex::Complex, progress::Timings, trap::Trap,
+};
+
+use crate::{client, user_agent};
+
+/// Default maximum length of a status on Mastodon.
+const MAX_STATUS_LENGTH: usize = 500;
+
+/// Parameters of a run, reported if it fails.
+#[derive(Debug, Default)]
+pub struct Run {
+    pub c: Option<Complex>,
+    pub palette: Option<PaletteChoice>,
+    pub connected: Option<bool>,
+    /// Trap of the render, if colored by a trap.
+    pub trap: Option<Trap>,
+    /// Average of the render, if colored by one.
+    pub average: Option<Average>,
+    /// Angle of the light, if the render has a relief.
+    pub light: Option<f64>,
+    /// Time spent in each phase finished so far.
+    pub timings: Timings,
+}
+
+impl Display for Run {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.c {
+            Some(c) => writeln!(f, "c = {c}")?,
+            None => writeln!(f, "c not sampled yet")?,
+        }
+        if let Some(palette) = self.palette {
+            writeln!(f, "palette: {palette}")?;
+        }
+        if let Some(connected) = self.connected {
+            writeln!(f, "connected: {connected}")?;
+        }
+        if let Some(trap) = self.trap {
+            writeln!(f, "trap: {trap}")?;
+        }
+        if let Some(average) = self.average {
+            writeln!(f, "average: {average}")?;
+        }
+        if let Some(light) = self.light {
+            writeln!(f, "light: {light:.0}°")?;
+        }
+        if self.timings.total() > Duration::ZERO {
+            writeln!(f, "timings: {}", self.timings)?;
+        }
+        Ok(())
+    }
+}
+
+/// Extract the message of a panic caught by [std::panic::catch_unwind].
+pub fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+/// Tells a human when a run fails: either an admin account via direct message,
+/// or a webhook if that is not configured or fails.
+pub struct FailureHandler {
+    admin: Option<String>,
+    webhook: Option<String>,
+}
+
+impl FailureHandler {
+    pub fn new(admin: Option<String>, webhook: Option<String>) -> Self {
+        Self { admin, webhook }
+    }
+
+    /// Send `message` to `admin`, cut short to fit a status.
+    async fn notify_admin(admin: &str, message: &str) -> Result<()> {
+        // Leave room for the mention prepended to the message.
+        let limit = MAX_STATUS_LENGTH.saturating_sub(admin.len() + 2);
+        let message: String = message.chars().take(limit).collect();
+        client()?.send_direct_message(admin, message).await
+    }
+
+    /// Report a failed run.
+    ///
+    /// Failures while reporting are only logged and never reported themselves,
+    /// so a broken handler (say, because of an expired token) cannot cause a loop.
+    pub fn report(&self, err: &Error, run: &Run) {
+        if self.admin.is_none() && self.webhook.is_none() {
+            return;
+        }
+
+        // Causes come first: direct messages are cut short, and the run's parameters
+        // are the less important part.
+        let causes: Vec<_> = err.chain().map(ToString::to_string).collect();
+        let message = format!(
+            "fractalbot run failed: {causes}\n\n{run}",
+            causes = causes.join("\ncaused by: ")
+        );
+
+        let rt = match tokio::runtime::Runtime::new() {
+            Ok(rt) => rt,
+            Err(err) => {
+                warn!("Failed to start runtime for failure report: {err}");
+                return;
+            }
+        };
+
+        if let Some(admin) = &self.admin {
+            match rt.block_on(Self::notify_admin(admin, &message)) {
+                Ok(()) => {
+                    info!("Reported failure to {admin}");
+                    return;
+                }
+                Err(err) => warn!("Failed to report failure to {admin}: {err:?}"),
+            }
+        }
+
+        if let Some(webhook) = &self.webhook {
+            match rt.block_on(fractalbot_post::post_webhook(
+                webhook,
+                &message,
+                user_agent(),
+            )) {
+                Ok(()) => info!("Reported failure to webhook"),
+                Err(err) => warn!("Failed to report failure to webhook: {err:?}"),
+            }
+        }
+    }
+}
@@ -1,36 +1,31 @@
-use std::io::Cursor;
-use std::ops::Deref;
-
-use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::panic::{self, AssertUnwindSafe};
+
+use anyhow::{Context, Result, anyhow};
+use fractalbot_post::Client;
+use fractalbot_render::{
+    RenderRequest, RenderedImage, coloring::Coloring, encode_png, progress::Timings,
+};
 use humansize::SizeFormatter;
-use image::{ImageBuffer, ImageFormat};
 use indoc::formatdoc;
-use log::{debug, info};
-use rand::RngExt;
-use rayon::prelude::{ParallelBridge, ParallelIterator};
-
-mod bounding_box;
-mod color;
-mod complex;
-mod distance_estimation;
+use log::{info, warn};
+use rand::Rng;
+use tokio::runtime::Runtime;
+
+mod auth;
 mod env;
-mod inverse_iteration;
+mod failure;
+mod poll;
+mod profile;
+mod reply;
 
 use crate::{
-    bounding_box::BoundingBox,
-    color::MonotonePalette,
-    complex::Complex,
-    distance_estimation::{DistanceEstimation, MandelbrotBoundary},
-    env::{Cmdline, Environment, Post},
-    inverse_iteration::InverseIteration,
+    env::{Action, Cmdline, Environment, Post},
+    failure::{FailureHandler, Run, panic_message},
+    poll::Vote,
 };
 
-/// Squeeze values in range [0, infty) into [0, 1).
-#[inline]
-fn squeeze(x: f64) -> f64 {
-    f64::exp(-x)
-}
-
 fn logger_init() {
     use env_logger::{Builder, Env};
 
@@ -47,139 +42,213 @@ fn logger_init() {
     Builder::from_env(env).init()
 }
 
+fn user_agent() -> String {
+    format!(
+        "fractalbot/{} (@phijor@types.pl)",
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+fn client() -> Result<fractalbot_post::Client> {
+    let env = Environment::from_env()?;
+    fractalbot_post::Client::new(env.instance_url, env.access_token, user_agent())
+        .context("Failed to initialize Mastodon client")
+}
+
 fn main() -> anyhow::Result<()> {
     logger_init();
 
-    const WIDTH: u32 = 1280;
-    const HEIGHT: u32 = 1280;
-    const ITER: usize = 10_000;
-    const MAX_ITER: usize = 4096;
-
     let mut rng = rand::rng();
 
     let cmdline: Cmdline = argh::from_env();
-    let c = cmdline
-        .parameter
-        .unwrap_or_else(|| rng.sample(MandelbrotBoundary { max_iter: MAX_ITER }));
+    let request = cmdline.render_request();
 
-    info!("Julia parameter: c = {c}");
-
-    let julia: InverseIteration = InverseIteration::new(c);
-
-    let mut bbx: BoundingBox = julia // Julia::new(Complex::new(-0.12, 0.74))
-        .into_iter()
-        .take(ITER)
-        .collect();
-    bbx.scale(1.20);
+    match cmdline.action {
+        Action::Save(save) => {
+            let request = RenderRequest {
+                width: save.width,
+                height: save.height,
+                ..request
+            };
 
-    info!(
-        "Bounding box has aspect ratio of {ratio:.2}:1",
-        ratio = bbx.aspect_ratio()
-    );
+            if save.stream {
+                let file = File::create(&save.path)
+                    .with_context(|| format!("Failed to create {}", save.path.display()))?;
+                info!("Streaming image to {}", save.path.display());
 
-    let imgbuf = {
-        let (width, height) = bbx.fit(WIDTH * 2, HEIGHT * 2);
-        let mut imgbuf = image::ImageBuffer::new(width, height);
-        let julia = DistanceEstimation::new(c, MAX_ITER);
-
-        let palette = rng.sample(MonotonePalette);
-        let sharpness = if julia.is_connected() {
-            info!("Julia set is connected");
-            25.0
-        } else {
-            info!("Julia set is disconnected");
-            100.0
-        };
-
-        debug!("Palette: {:.2?}", palette);
-        debug!("Color for d=0.0: {:?}", palette.pick(0.0));
-
-        let set_color = move |(pixel, point): (&mut _, Complex)| {
-            let d: f64 = julia.distance(point);
-            *pixel = if d <= 0.0 {
-                image::Rgb([0, 0, 0])
-            } else {
-                let d = squeeze((sharpness * d).sqrt());
-                palette.pick(d)
-            };
-        };
+                let mut writer = BufWriter::new(file);
+                let timings = request.render_png(&mut rng, &mut writer)?;
+                writer
+                    .flush()
+                    .with_context(|| format!("Failed to save image to {}", save.path.display()))?;
 
-        bbx.points(&mut imgbuf).par_bridge().for_each(set_color);
+                info!("Timings: {timings}");
+                return Ok(());
+            }
 
-        info!("Downscaling supersampled image...");
-        image::imageops::resize(
-            &imgbuf,
-            width / 2,
-            height / 2,
-            image::imageops::FilterType::Triangle,
-        )
-    };
+            let mut rendered = request.render(&mut rng)?;
 
-    match cmdline.action {
-        env::Action::Save(save) => {
             info!("Saving image to {}", save.path.display());
-            imgbuf
-                .save(&save.path)
-                .with_context(|| format!("Failed to save image to {}", save.path.display()))
-        }
-        env::Action::Post(Post { status_visibility }) => {
-            let description = formatdoc! {r#"
-                Julia set of the day:
-                \[
-                    c = {c}
-                \]
+            rendered
+                .timings
+                .time("encoding", || rendered.image.save(&save.path))
+                .with_context(|| format!("Failed to save image to {}", save.path.display()))?;
 
-                #fractal #generative
-            "#};
+            info!("Timings: {}", rendered.timings);
+            Ok(())
+        }
+        Action::Tiles(tiles) => {
+            let request = RenderRequest {
+                width: tiles.width,
+                height: tiles.height,
+                ..request
+            };
+            let timings = request.render_pyramid(&mut rng, &tiles.path)?;
 
-            post_status(imgbuf, description, status_visibility)
+            info!("Timings: {timings}");
+            Ok(())
         }
+        Action::Post(post) => {
+            let failure =
+                FailureHandler::new(post.notify_admin.clone(), post.notify_webhook.clone());
+            let mut run = Run::default();
+
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                post_daily(&mut rng, request, post, &mut run)
+            }))
+            .unwrap_or_else(|payload| Err(anyhow!("Run panicked: {}", panic_message(&*payload))));
+
+            match &result {
+                Ok(()) => info!("Run summary:\n{run}"),
+                Err(err) => failure.report(err, &run),
+            }
+            result
+        }
+        Action::Reply(options) => reply::reply(client()?, request, options),
+        Action::Auth(auth) => auth::auth(auth.action),
     }
 }
 
-fn encode_png<Container>(imgbuf: ImageBuffer<image::Rgb<u8>, Container>) -> Result<&'static [u8]>
-where
-    Container: Deref<Target = [u8]>,
-{
-    let mut encode_buffer = Cursor::new(Vec::new());
-    imgbuf
-        .write_to(&mut encode_buffer, ImageFormat::Png)
-        .context("Failed to encode image")?;
-    let buf = encode_buffer.into_inner().into_boxed_slice();
-
-    Ok(Box::leak(buf))
-}
+fn post_daily<R: Rng + ?Sized>(
+    rng: &mut R,
+    mut request: RenderRequest,
+    options: Post,
+    run: &mut Run,
+) -> Result<()> {
+    let client = client()?;
+    let rt = tokio::runtime::Runtime::new().unwrap();
+
+    let vote = match poll::read_state(&options.poll_state)? {
+        Some(status_id) => match rt.block_on(client.poll(status_id)) {
+            Ok(Some(poll)) => poll::winner(&poll, rng),
+            Ok(None) => None,
+            Err(err) => {
+                warn!("Ignoring last poll: {err:?}");
+                None
+            }
+        },
+        None => None,
+    };
+
+    match vote {
+        Some(Vote::Palette(palette)) => request.palette = palette,
+        Some(Vote::Connected(connected)) => request.connected = Some(connected),
+        None => {}
+    }
+
+    run.palette = Some(request.palette);
+    run.connected = request.connected;
+
+    // Fix the parameter before rendering, so that it can be reported if rendering fails.
+    let request = request.plan(rng, &mut run.timings);
+    let c = request.sample_parameter(rng);
+    run.palette = Some(request.palette);
+    run.c = Some(c);
+    run.trap = (request.sampling.coloring == Coloring::Trap).then_some(request.sampling.trap);
+    run.average = request.sampling.coloring.average(&request.sampling);
+    run.light = request.sampling.relief.then_some(request.sampling.light);
+
+    let rendered = request.render_planned(rng)?;
+    run.timings.extend(&rendered.timings);
+
+    let description = if request.sampling.mandelbrot {
+        formatdoc! {"
+            Mandelbrot set of the day
+
+            #fractal #generative
+        "}
+    } else {
+        formatdoc! {r#"
+            Julia set of the day:
+            \[
+                c = {c}
+            \]
+
+            #fractal #generative
+        "#}
+    };
 
-fn post_status<Container>(
-    imgbuf: ImageBuffer<image::Rgb<u8>, Container>,
-    description: String,
-    visibility: fractalbot_post::StatusVisibility,
-) -> Result<()>
-where
-    Container: Deref<Target = [u8]>,
-{
     info!("Encoding image");
-    let encoded_image = encode_png(imgbuf)?;
+    let encoded_image = run
+        .timings
+        .time("encoding", || encode_png(&rendered.image))?;
 
     info!(
         "Posting image to fediverse (size: {})",
         SizeFormatter::new(encoded_image.len(), humansize::DECIMAL)
     );
 
-    let user_agent = format!(
-        "fractalbot/{} (@phijor@types.pl)",
-        env!("CARGO_PKG_VERSION")
-    );
-    let env = Environment::from_env()?;
-    let client = fractalbot_post::Client::new(env.instance_url, env.access_token, user_agent)
-        .context("Failed to initialize Mastodon client")?;
+    let status_id = run
+        .timings
+        .time("upload", || {
+            rt.block_on(client.post_status_with_image(
+                encoded_image.into(),
+                "fractal.png",
+                description,
+                options.status_visibility.clone(),
+            ))
+        })
+        .context("Failed to post image")?;
+
+    // The vote of the previous poll is used up: forget the poll, so that its vote
+    // is not applied again if anything below fails.
+    poll::clear_state(&options.poll_state)?;
+
+    if options.update_profile {
+        // The image is posted already: a failed update must not keep the poll from being posted.
+        if let Err(err) = update_profile(&rt, &client, &rendered, &mut run.timings) {
+            warn!("Failed to update profile images: {err:?}");
+        }
+    }
 
-    let rt = tokio::runtime::Runtime::new().unwrap();
-    rt.block_on(client.post_status_with_image(
-        encoded_image,
-        "fractal.png",
-        description,
-        visibility,
-    ))
-    .context("Failed to post image")
+    if !options.poll {
+        return Ok(());
+    }
+
+    let (question, choices) = poll::sample_poll(rng);
+    let poll_status_id = run.timings.time("upload", || {
+        rt.block_on(client.post_poll(
+            status_id,
+            question,
+            choices,
+            poll::POLL_DURATION,
+            options.status_visibility,
+        ))
+    })?;
+
+    poll::write_state(&options.poll_state, &poll_status_id)
+}
+
+/// Use `rendered` as the account's avatar and header.
+fn update_profile(
+    rt: &Runtime,
+    client: &Client,
+    rendered: &RenderedImage,
+    timings: &mut Timings,
+) -> Result<()> {
+    let avatar = profile::profile_image(rendered, profile::AVATAR_SIZE)?;
+    let header = profile::profile_image(rendered, profile::HEADER_SIZE)?;
+    timings.time("upload", || {
+        rt.block_on(client.update_profile_images(Some(avatar.into()), Some(header.into())))
+    })
 }
@@ -0,0 +1,114 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, Error, Result};
+use fractalbot_post::Poll;
+use log::{info, warn};
+use rand::{Rng, RngExt, seq::IndexedRandom};
+
+use fractalbot_render::color::{DEFAULT_PALETTE_NAMES, PaletteChoice};
+
+/// Polls close a bit before the next daily run.
+pub const POLL_DURATION: u64 = 23 * 60 * 60;
+
+/// Number of choices in a poll; Mastodon allows at most four by default.
+const MAX_CHOICES: usize = 4;
+
+/// A poll choice constraining the next render.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Vote {
+    Palette(PaletteChoice),
+    Connected(bool),
+}
+
+impl FromStr for Vote {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "connected" => Ok(Self::Connected(true)),
+            "disconnected" => Ok(Self::Connected(false)),
+            palette => palette.parse().map(Self::Palette),
+        }
+    }
+}
+
+/// Sample a question and its choices for tomorrow's poll.
+pub fn sample_poll<R: Rng + ?Sized>(rng: &mut R) -> (String, Vec<String>) {
+    if rng.random_ratio(1, 3) {
+        let question = "Should tomorrow's Julia set be connected or disconnected?";
+        let choices = vec!["connected".into(), "disconnected".into()];
+        return (question.into(), choices);
+    }
+
+    let palettes: Vec<&str> = DEFAULT_PALETTE_NAMES
+        .into_iter()
+        .chain(["monotone", "phase-shift"])
+        .collect();
+    let choices = palettes
+        .sample(rng, MAX_CHOICES)
+        .map(|&palette| palette.into())
+        .collect();
+
+    (
+        "Which palette should tomorrow's Julia set use?".into(),
+        choices,
+    )
+}
+
+/// Determine the winning vote of a poll, breaking ties randomly.
+///
+/// Returns `None` if nobody voted.
+pub fn winner<R: Rng + ?Sized>(poll: &Poll, rng: &mut R) -> Option<Vote> {
+    let max_votes = poll
+        .options
+        .iter()
+        .map(|option| option.votes_count.unwrap_or(0))
+        .max()?;
+    if max_votes == 0 {
+        info!("Nobody voted in the last poll");
+        return None;
+    }
+
+    let tied: Vec<_> = poll
+        .options
+        .iter()
+        .filter(|option| option.votes_count.unwrap_or(0) == max_votes)
+        .collect();
+    let winner = tied.choose(rng)?;
+
+    info!("Poll winner: {} ({max_votes} votes)", winner.title);
+    winner
+        .title
+        .parse()
+        .inspect_err(|err| warn!("Ignoring poll winner: {err}"))
+        .ok()
+}
+
+/// Read the ID of the status containing the last poll, if there is one.
+pub fn read_state(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(status_id) => Ok(Some(status_id.trim().into())),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => {
+            Err(err).with_context(|| format!("Failed to read poll state from {}", path.display()))
+        }
+    }
+}
+
+/// Remember the ID of the status containing the poll for the next run.
+pub fn write_state(path: &Path, status_id: &str) -> Result<()> {
+    fs::write(path, status_id)
+        .with_context(|| format!("Failed to write poll state to {}", path.display()))
+}
+
+pub fn clear_state(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => {
+            Err(err).with_context(|| format!("Failed to remove poll state {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
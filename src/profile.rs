@@ -0,0 +1,51 @@
+use anyhow::{Result, bail};
+use image::imageops::{self, FilterType};
+use log::{debug, info};
+
+use fractalbot_render::{RenderedImage, encode_png};
+
+/// Size (in pixels) Mastodon scales avatars to.
+pub const AVATAR_SIZE: (u32, u32) = (400, 400);
+
+/// Size (in pixels) Mastodon scales headers to.
+pub const HEADER_SIZE: (u32, u32) = (1500, 500);
+
+/// Mastodon rejects profile images larger than this (in bytes) by default.
+const MAX_FILE_SIZE: usize = 2_000_000;
+
+/// Profile images are not shrunk below this size (in pixels) on either side.
+const MIN_SIZE: u32 = 64;
+
+/// Crop `rendered` to the aspect ratio of `size`, centered on the set's bounding box,
+/// and encode it as a PNG no larger than `size` and [MAX_FILE_SIZE].
+pub fn profile_image(rendered: &RenderedImage, size: (u32, u32)) -> Result<Vec<u8>> {
+    let RenderedImage { bbx, image, .. } = rendered;
+    let (max_width, max_height) = size;
+
+    let crop = bbx.crop_to(f64::from(max_width) / f64::from(max_height));
+    let (x, y, width, height) = bbx.grid_rect(&crop, image.width(), image.height());
+    let cropped = imageops::crop_imm(image, x, y, width, height).to_image();
+
+    // Only ever downscale the crop, and shrink it further until it fits the file size limit.
+    let mut scale = f64::min(1.0, f64::from(max_width) / f64::from(cropped.width()));
+    loop {
+        let width = (f64::from(cropped.width()) * scale) as u32;
+        let height = (f64::from(cropped.height()) * scale) as u32;
+        if width.min(height) < MIN_SIZE {
+            bail!("Profile image does not fit in {MAX_FILE_SIZE} bytes even at {width}x{height}");
+        }
+        let resized = imageops::resize(&cropped, width, height, FilterType::Triangle);
+
+        let encoded = encode_png(&resized)?;
+        if encoded.len() <= MAX_FILE_SIZE {
+            info!("Profile image has size {width}x{height}");
+            return Ok(encoded);
+        }
+
+        debug!(
+            "Profile image of size {width}x{height} too large ({} bytes)",
+            encoded.len()
+        );
+        scale *= 0.8;
+    }
+}
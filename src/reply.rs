@@ -0,0 +1,353 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Error, Result};
+use fractalbot_post::{Client, Mention};
+use fractalbot_render::{
+    RenderRequest, Sampling, color::PaletteChoice, complex::Complex, encode_png,
+};
+use indoc::formatdoc;
+use log::{info, warn};
+use tokio::sync::Semaphore;
+use tokio::task::{Id, JoinError, JoinSet};
+
+use crate::env::Reply;
+
+/// Well-known Julia sets that can be requested by name.
+const PRESETS: [(&str, Complex); 7] = [
+    ("basilica", Complex::new(-1.0, 0.0)),
+    ("rabbit", Complex::new(-0.122_561, 0.744_862)),
+    ("dendrite", Complex::new(0.0, 1.0)),
+    ("airplane", Complex::new(-1.754_878, 0.0)),
+    ("san-marco", Complex::new(-0.75, 0.0)),
+    ("siegel", Complex::new(-0.390_541, -0.586_788)),
+    ("cauliflower", Complex::new(0.25, 0.0)),
+];
+
+/// What a follower asked for in a mention.
+#[derive(Debug, Default, PartialEq)]
+struct Request {
+    c: Option<Complex>,
+    palette: Option<PaletteChoice>,
+}
+
+impl Request {
+    /// Parse a request from the HTML content of a mention.
+    ///
+    /// Words are matched against the names of [PRESETS] and palettes,
+    /// or parsed as the complex parameter (optionally prefixed by `c=`).
+    /// The first match of each kind wins.
+    fn parse(content: &str) -> Option<Self> {
+        let mut request = Self::default();
+
+        for word in strip_html(content).split_whitespace() {
+            if word.starts_with('@') {
+                continue;
+            }
+
+            let word = word.trim_end_matches(['?', '!', ',', ';', ':', '.', ')', '"']);
+            let word = word.trim_start_matches(['(', '"']);
+            let word = word.strip_prefix("c=").unwrap_or(word);
+
+            if let Some((_, c)) = PRESETS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(word))
+            {
+                request.c.get_or_insert(*c);
+            } else if let Ok(palette) = PaletteChoice::from_str(word) {
+                request.palette.get_or_insert(palette);
+            } else if word.contains(|ch: char| ch.is_ascii_digit())
+                && let Ok(c) = Complex::from_str(word)
+            {
+                request.c.get_or_insert(c);
+            }
+        }
+
+        (request != Self::default()).then_some(request)
+    }
+}
+
+/// Whether a mention asks for help, i.e. contains the word "help".
+///
+/// Only such mentions are answered with [help_text] when they contain no request,
+/// so that the bot stays quiet when mentioned in passing, say in a thread.
+fn asks_for_help(content: &str) -> bool {
+    strip_html(content)
+        .split_whitespace()
+        .filter(|word| !word.starts_with('@'))
+        .any(|word| {
+            word.trim_matches(|ch: char| !ch.is_alphanumeric())
+                .eq_ignore_ascii_case("help")
+        })
+}
+
+/// Remove HTML tags and decode the few entities Mastodon uses in status content.
+fn strip_html(content: &str) -> String {
+    let mut text = String::with_capacity(content.len());
+    let mut in_tag = false;
+    for ch in content.chars() {
+        match ch {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            ch if !in_tag => text.push(ch),
+            _ => {}
+        }
+    }
+
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+fn normalize_account(account: &str) -> String {
+    account.trim_start_matches('@').to_lowercase()
+}
+
+/// Decides which accounts the bot answers to.
+struct AccessList {
+    allow: HashSet<String>,
+    deny: HashSet<String>,
+}
+
+impl AccessList {
+    fn new(allow: &[String], deny: &[String]) -> Self {
+        Self {
+            allow: allow.iter().map(|acct| normalize_account(acct)).collect(),
+            deny: deny.iter().map(|acct| normalize_account(acct)).collect(),
+        }
+    }
+
+    /// An account is permitted if it is not denied, and either on the
+    /// allow list or no allow list was given.
+    fn permits(&self, account: &str) -> bool {
+        let account = normalize_account(account);
+        !self.deny.contains(&account) && (self.allow.is_empty() || self.allow.contains(&account))
+    }
+}
+
+/// A sliding-window rate limit on renders per account.
+struct RateLimit {
+    limit: usize,
+    window: Duration,
+    history: HashMap<String, VecDeque<Instant>>,
+    /// Notifications charged already, so that retrying a failed answer is free.
+    charged: HashSet<String>,
+}
+
+impl RateLimit {
+    fn new(limit: usize, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            history: HashMap::new(),
+            charged: HashSet::new(),
+        }
+    }
+
+    /// Record a request by `account` in the notification `notification_id`, returning
+    /// `false` if it exceeds the limit. Each notification is only charged once.
+    fn check(&mut self, account: &str, notification_id: &str) -> bool {
+        if self.charged.contains(notification_id) {
+            return true;
+        }
+
+        let now = Instant::now();
+        let history = self.history.entry(normalize_account(account)).or_default();
+
+        while history
+            .front()
+            .is_some_and(|&time| now.duration_since(time) > self.window)
+        {
+            history.pop_front();
+        }
+
+        if history.len() >= self.limit {
+            return false;
+        }
+
+        history.push_back(now);
+        self.charged.insert(notification_id.to_owned());
+        true
+    }
+
+    /// Forget the charge for a notification that was answered (and so is not fetched again).
+    fn answered(&mut self, notification_id: &str) {
+        self.charged.remove(notification_id);
+    }
+}
+
+fn help_text(mention: &Mention) -> String {
+    let presets = PRESETS.map(|(name, _)| name).join(", ");
+    formatdoc! {"
+        @{account} I did not understand which fractal you'd like to see.
+
+        Mention me with a parameter like c = 0.28+0.01i or one of {presets}, \
+        and optionally a palette (rainbow, whites, arctic, citrus, dusk, pink, glow).
+        ",
+        account = mention.account,
+    }
+}
+
+async fn answer(
+    client: Arc<Client>,
+    renders: Arc<Semaphore>,
+    base: RenderRequest,
+    mention: Mention,
+    request: Request,
+) -> Result<()> {
+    let permit = renders.acquire_owned().await?;
+
+    info!(
+        "Rendering {request:?} for {account}",
+        account = mention.account
+    );
+    let (c, encoded_image) = tokio::task::spawn_blocking(move || {
+        let _permit = permit;
+
+        let rendered = RenderRequest {
+            parameter: request.c,
+            palette: request.palette.unwrap_or(base.palette),
+            // Followers ask for Julia sets.
+            sampling: Sampling {
+                mandelbrot: false,
+                ..base.sampling
+            },
+            ..base
+        }
+        .render(&mut rand::rng())?;
+
+        encode_png(&rendered.image).map(|encoded_image| (rendered.c, encoded_image))
+    })
+    .await??;
+
+    let description = formatdoc! {r#"
+        @{account} Here is your Julia set:
+        \[
+            c = {c}
+        \]
+    "#,
+        account = mention.account,
+    };
+
+    client
+        .reply_with_image(&mention, encoded_image.into(), "fractal.png", description)
+        .await?;
+    client.dismiss_notification(&mention.notification_id).await
+}
+
+async fn serve(client: Arc<Client>, base: RenderRequest, options: Reply) -> Result<()> {
+    let access = AccessList::new(&options.allow, &options.deny);
+    let mut rate_limit = RateLimit::new(
+        options.rate_limit,
+        Duration::from_secs(options.rate_limit_window),
+    );
+    let renders = Arc::new(Semaphore::new(options.max_concurrent.max(1)));
+    let mut pending = JoinSet::new();
+    // Notifications of the mentions being answered, by the task answering them.
+    let mut in_flight = HashMap::new();
+
+    loop {
+        while let Some(result) = pending.try_join_next_with_id() {
+            settle(&mut in_flight, &mut rate_limit, result);
+        }
+
+        // Handled mentions are dismissed and not fetched again. Mentions whose
+        // answer failed are not, and are retried.
+        let mentions = match client.mentions(None).await {
+            Ok(mentions) => mentions,
+            Err(err) => {
+                warn!("Failed to fetch mentions: {err:?}");
+                Vec::new()
+            }
+        };
+
+        for mention in mentions {
+            if in_flight.values().any(|id| *id == mention.notification_id) {
+                continue;
+            }
+            if pending.len() >= options.max_queued {
+                info!("Queue is full, deferring remaining mentions");
+                break;
+            }
+
+            let notification_id = mention.notification_id.clone();
+            if !access.permits(&mention.account) {
+                info!("Ignoring mention by {}", mention.account);
+            } else if let Some(request) = Request::parse(&mention.content) {
+                // Only renders count towards the limit.
+                if rate_limit.check(&mention.account, &notification_id) {
+                    let task = pending.spawn(answer(
+                        client.clone(),
+                        renders.clone(),
+                        base.clone(),
+                        mention,
+                        request,
+                    ));
+                    in_flight.insert(task.id(), notification_id);
+                    continue;
+                }
+                info!("Rate limit exceeded by {}", mention.account);
+            } else if !mention.bot && asks_for_help(&mention.content) {
+                let client = client.clone();
+                let task = pending.spawn(async move {
+                    client.reply(&mention, help_text(&mention)).await?;
+                    client.dismiss_notification(&mention.notification_id).await
+                });
+                in_flight.insert(task.id(), notification_id);
+                continue;
+            } else {
+                info!("Ignoring mention without a request by {}", mention.account);
+            }
+
+            if let Err(err) = client.dismiss_notification(&notification_id).await {
+                warn!("{err:?}");
+            }
+        }
+
+        if options.once {
+            while let Some(result) = pending.join_next_with_id().await {
+                settle(&mut in_flight, &mut rate_limit, result);
+            }
+            return Ok(());
+        }
+
+        tokio::time::sleep(Duration::from_secs(options.poll_interval)).await;
+    }
+}
+
+/// Forget the notification of a finished reply task, logging why it failed if it did.
+fn settle(
+    in_flight: &mut HashMap<Id, String>,
+    rate_limit: &mut RateLimit,
+    result: Result<(Id, Result<()>), JoinError>,
+) {
+    let (id, result) = match result {
+        Ok((id, result)) => (id, result),
+        Err(err) => (
+            err.id(),
+            Err(Error::new(err).context("Reply task panicked")),
+        ),
+    };
+    let notification_id = in_flight.remove(&id);
+    match result {
+        Ok(()) => {
+            if let Some(notification_id) = notification_id {
+                rate_limit.answered(&notification_id);
+            }
+        }
+        Err(err) => warn!("Failed to answer mention: {err:?}"),
+    }
+}
+
+/// Answer mentions, rendering with the settings of `base` unless overridden by the mention.
+pub fn reply(client: Client, base: RenderRequest, options: Reply) -> Result<()> {
+    let rt = tokio::runtime::Runtime::new().context("Failed to start async runtime")?;
+    rt.block_on(serve(Arc::new(client), base, options))
+}